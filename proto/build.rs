@@ -13,6 +13,9 @@ pub fn main() {
             println!("cargo:rerun-if-changed={proto_file}");
             prost_build::Config::new()
                 .out_dir("out")
+                .enable_type_names()
+                .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+                .message_attribute(".", "#[serde(default)]")
                 .compile_protos(&[proto_file], &["."])
                 .unwrap();
         }
@@ -0,0 +1,339 @@
+// This file is @generated by prost-build.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Status {
+    #[prost(enumeration = "StatusCode", tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+impl ::prost::Name for Status {
+    const NAME: &'static str = "Status";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.Status".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.Status".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeartbeatMsg {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+impl ::prost::Name for HeartbeatMsg {
+    const NAME: &'static str = "HeartbeatMsg";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.HeartbeatMsg".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.HeartbeatMsg".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RandomNumberRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(int32, tag = "2")]
+    pub min: i32,
+    #[prost(int32, tag = "3")]
+    pub max: i32,
+    #[prost(int32, tag = "4")]
+    pub interval: i32,
+    #[prost(string, tag = "5")]
+    pub shared_key: ::prost::alloc::string::String,
+}
+impl ::prost::Name for RandomNumberRequest {
+    const NAME: &'static str = "RandomNumberRequest";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.RandomNumberRequest".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.RandomNumberRequest".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StopRandomNumberRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+}
+impl ::prost::Name for StopRandomNumberRequest {
+    const NAME: &'static str = "StopRandomNumberRequest";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.StopRandomNumberRequest".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.StopRandomNumberRequest".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IncrementalSequenceRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(int32, tag = "2")]
+    pub start: i32,
+    #[prost(int32, tag = "3")]
+    pub end: i32,
+    #[prost(int32, tag = "4")]
+    pub interval: i32,
+    #[prost(string, tag = "5")]
+    pub shared_key: ::prost::alloc::string::String,
+}
+impl ::prost::Name for IncrementalSequenceRequest {
+    const NAME: &'static str = "IncrementalSequenceRequest";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.IncrementalSequenceRequest".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.IncrementalSequenceRequest".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StopIncrementalSequenceRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+}
+impl ::prost::Name for StopIncrementalSequenceRequest {
+    const NAME: &'static str = "StopIncrementalSequenceRequest";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.StopIncrementalSequenceRequest".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.StopIncrementalSequenceRequest".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EchoRequest {
+    #[prost(string, tag = "1")]
+    pub message: ::prost::alloc::string::String,
+}
+impl ::prost::Name for EchoRequest {
+    const NAME: &'static str = "EchoRequest";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.EchoRequest".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.EchoRequest".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RandomNumberResponse {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+    #[prost(int32, tag = "3")]
+    pub number: i32,
+}
+impl ::prost::Name for RandomNumberResponse {
+    const NAME: &'static str = "RandomNumberResponse";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.RandomNumberResponse".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.RandomNumberResponse".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StopRandomNumberResponse {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+}
+impl ::prost::Name for StopRandomNumberResponse {
+    const NAME: &'static str = "StopRandomNumberResponse";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.StopRandomNumberResponse".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.StopRandomNumberResponse".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IncrementalSequenceResponse {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+    #[prost(int32, tag = "3")]
+    pub number: i32,
+}
+impl ::prost::Name for IncrementalSequenceResponse {
+    const NAME: &'static str = "IncrementalSequenceResponse";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.IncrementalSequenceResponse".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.IncrementalSequenceResponse".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StopIncrementalSequenceResponse {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+}
+impl ::prost::Name for StopIncrementalSequenceResponse {
+    const NAME: &'static str = "StopIncrementalSequenceResponse";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.StopIncrementalSequenceResponse".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.StopIncrementalSequenceResponse".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EchoResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status: ::core::option::Option<Status>,
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+impl ::prost::Name for EchoResponse {
+    const NAME: &'static str = "EchoResponse";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.EchoResponse".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.EchoResponse".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SessionInfoNotify {
+    #[prost(uint64, tag = "1")]
+    pub session_id: u64,
+    #[prost(string, tag = "2")]
+    pub resume_token: ::prost::alloc::string::String,
+    #[prost(bool, tag = "3")]
+    pub resumed: bool,
+    #[prost(uint64, tag = "4")]
+    pub last_seq: u64,
+}
+impl ::prost::Name for SessionInfoNotify {
+    const NAME: &'static str = "SessionInfoNotify";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.SessionInfoNotify".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.SessionInfoNotify".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CommandStatusNotify {
+    #[prost(uint32, tag = "1")]
+    pub cmd_id: u32,
+    #[prost(message, optional, tag = "2")]
+    pub status: ::core::option::Option<Status>,
+}
+impl ::prost::Name for CommandStatusNotify {
+    const NAME: &'static str = "CommandStatusNotify";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.CommandStatusNotify".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.CommandStatusNotify".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServerShutdownNotice {
+    #[prost(string, tag = "1")]
+    pub message: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub reconnect_after_secs: u32,
+}
+impl ::prost::Name for ServerShutdownNotice {
+    const NAME: &'static str = "ServerShutdownNotice";
+    const PACKAGE: &'static str = "msg";
+    fn full_name() -> ::prost::alloc::string::String {
+        "msg.ServerShutdownNotice".into()
+    }
+    fn type_url() -> ::prost::alloc::string::String {
+        "/msg.ServerShutdownNotice".into()
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum StatusCode {
+    Success = 0,
+    Failure = 1,
+    InvalidRequest = 2,
+    ServerError = 3,
+    Cancelled = 4,
+    RateLimited = 5,
+}
+impl StatusCode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Success => "SUCCESS",
+            Self::Failure => "FAILURE",
+            Self::InvalidRequest => "INVALID_REQUEST",
+            Self::ServerError => "SERVER_ERROR",
+            Self::Cancelled => "CANCELLED",
+            Self::RateLimited => "RATE_LIMITED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SUCCESS" => Some(Self::Success),
+            "FAILURE" => Some(Self::Failure),
+            "INVALID_REQUEST" => Some(Self::InvalidRequest),
+            "SERVER_ERROR" => Some(Self::ServerError),
+            "CANCELLED" => Some(Self::Cancelled),
+            "RATE_LIMITED" => Some(Self::RateLimited),
+            _ => None,
+        }
+    }
+}
@@ -19,3 +19,9 @@ pub const INCREMENTAL_SEQUENCE_RESPONSE: u16 = 9;
 pub const STOP_INCREMENTAL_SEQUENCE_RESPONSE: u16 = 10;
 
 pub const ECHO_RESPONSE: u16 = 11;
+
+pub const SESSION_INFO_NOTIFY: u16 = 12;
+
+pub const SERVER_SHUTDOWN_NOTICE: u16 = 13;
+
+pub const COMMAND_STATUS_NOTIFY: u16 = 14;
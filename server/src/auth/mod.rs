@@ -0,0 +1,73 @@
+mod policy;
+mod token;
+
+use std::sync::Arc;
+use axum::http::{header, HeaderMap};
+use crate::config::server_config::{AuthConfig, AuthMode};
+use token::{HmacTokenAuthenticator, StaticTokenAuthenticator};
+
+pub use policy::Policy;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+impl Identity {
+    pub fn anonymous() -> Self {
+        Self {
+            name: "anonymous".to_string(),
+            roles: Vec::new(),
+        }
+    }
+}
+
+/// Validates the bearer token presented when a WebSocket is upgraded.
+pub trait Authenticator: Send + Sync {
+    /// Returns the identity owning `token`, or `None` to reject the upgrade.
+    fn authenticate(&self, token: Option<&str>) -> Option<Identity>;
+}
+
+struct NoAuthenticator;
+
+impl Authenticator for NoAuthenticator {
+    fn authenticate(&self, _token: Option<&str>) -> Option<Identity> {
+        Some(Identity::anonymous())
+    }
+}
+
+pub fn create_authenticator(config: &AuthConfig) -> Arc<dyn Authenticator> {
+    match config.mode {
+        AuthMode::None => Arc::new(NoAuthenticator),
+        AuthMode::Static => Arc::new(StaticTokenAuthenticator::new(&config.tokens)),
+        AuthMode::Hmac => Arc::new(HmacTokenAuthenticator::new(&config.hmac_secret)),
+    }
+}
+
+/// Looks for a bearer token in the `Authorization` header, then the `token`
+/// query parameter and finally the cookie named `cookie_name`.
+pub fn extract_token(headers: &HeaderMap, query_token: Option<&str>, cookie_name: &str) -> Option<String> {
+    let bearer = headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    if let Some(token) = bearer {
+        return Some(token.to_string());
+    }
+    if let Some(token) = query_token {
+        return Some(token.to_string());
+    }
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Compares secrets in a time independent of where they differ.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
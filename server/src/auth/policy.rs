@@ -0,0 +1,32 @@
+use std::collections::{HashMap, HashSet};
+use crate::config::server_config::AuthorizationConfig;
+use super::Identity;
+
+/// Maps roles to the command ids they are allowed to invoke.
+pub struct Policy {
+    enabled: bool,
+    default_roles: Vec<String>,
+    roles: HashMap<String, HashSet<u16>>,
+}
+
+impl Policy {
+    pub fn new(config: &AuthorizationConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            default_roles: config.default_roles.clone(),
+            roles: config.roles.iter()
+                .map(|(role, cmd_ids)| (role.clone(), cmd_ids.iter().copied().collect()))
+                .collect(),
+        }
+    }
+
+    pub fn permits(&self, identity: &Identity, cmd_id: u16) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        self.default_roles.iter()
+            .chain(identity.roles.iter())
+            .filter_map(|role| self.roles.get(role))
+            .any(|cmd_ids| cmd_ids.contains(&cmd_id))
+    }
+}
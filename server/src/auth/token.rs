@@ -0,0 +1,81 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use crate::config::server_config::StaticToken;
+use super::{constant_time_eq, Authenticator, Identity};
+
+/// Accepts the tokens listed in the configuration.
+pub struct StaticTokenAuthenticator {
+    tokens: Vec<(String, Identity)>,
+}
+
+impl StaticTokenAuthenticator {
+    pub fn new(tokens: &[StaticToken]) -> Self {
+        let tokens = tokens.iter()
+            .map(|token| (token.token.clone(), Identity {
+                name: token.identity.clone(),
+                roles: token.roles.clone(),
+            }))
+            .collect();
+        Self { tokens }
+    }
+}
+
+impl Authenticator for StaticTokenAuthenticator {
+    fn authenticate(&self, token: Option<&str>) -> Option<Identity> {
+        let token = token?;
+        self.tokens.iter()
+            .find(|(candidate, _)| constant_time_eq(candidate.as_bytes(), token.as_bytes()))
+            .map(|(_, identity)| identity.clone())
+    }
+}
+
+#[derive(Deserialize)]
+struct HmacClaims {
+    sub: String,
+    #[serde(default)]
+    roles: Vec<String>,
+    exp: Option<i64>,
+}
+
+/// Accepts tokens of the form `<payload>.<signature>`, where `payload` is the
+/// base64url encoded JSON `{"sub": ..., "roles": [...], "exp": ...}` and
+/// `signature` the base64url encoded HMAC-SHA256 of `payload` with the secret.
+pub struct HmacTokenAuthenticator {
+    secret: Vec<u8>,
+}
+
+impl HmacTokenAuthenticator {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    fn verify(&self, token: &str) -> Option<HmacClaims> {
+        let (payload, signature) = token.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).ok()?;
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let claims: HmacClaims = serde_json::from_slice(&payload).ok()?;
+        match claims.exp {
+            Some(exp) if exp <= Utc::now().timestamp() => None,
+            _ => Some(claims),
+        }
+    }
+}
+
+impl Authenticator for HmacTokenAuthenticator {
+    fn authenticate(&self, token: Option<&str>) -> Option<Identity> {
+        let claims = self.verify(token?)?;
+        Some(Identity {
+            name: claims.sub,
+            roles: claims.roles,
+        })
+    }
+}
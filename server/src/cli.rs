@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use server::config::ConfigOverrides;
+
+#[derive(Parser)]
+#[command(version, about = "FATE/LOOM server")]
+pub struct Cli {
+    /// Path of the configuration file [default: server.toml]
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Host to listen on, overrides `host`
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+
+    /// Port to listen on, overrides `port`
+    #[arg(short, long, global = true)]
+    pub port: Option<u32>,
+
+    /// Log filter such as `debug` or `info,server::net=trace`, overrides `log.filter`
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// Overrides any configuration key, e.g. `--set http.dist_path=/srv/dist`
+    #[arg(short = 's', long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub values: Vec<(String, String)>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum Command {
+    /// Run the server (default)
+    Serve,
+    /// Validate the configuration and print the effective configuration
+    CheckConfig {
+        /// Print where each key comes from instead of the configuration
+        #[arg(long)]
+        sources: bool,
+    },
+    /// Print the default configuration
+    PrintDefaultConfig,
+    /// Write the default configuration to the configuration file
+    Init {
+        /// Overwrite an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Cli {
+    pub fn overrides(&self) -> ConfigOverrides {
+        let mut values = Vec::new();
+        if let Some(host) = &self.host {
+            values.push(("host".to_string(), host.clone()));
+        }
+        if let Some(port) = self.port {
+            values.push(("port".to_string(), port.to_string()));
+        }
+        if let Some(log_level) = &self.log_level {
+            values.push(("log.filter".to_string(), log_level.clone()));
+        }
+        values.extend(self.values.iter().cloned());
+        ConfigOverrides {
+            path: self.config.clone(),
+            values,
+        }
+    }
+}
+
+fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{arg}`"))
+}
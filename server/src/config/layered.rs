@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde_json::{Map, Value};
+
+const ENV_PREFIX: &str = "SERVER__";
+const ENV_SEPARATOR: &str = "__";
+
+/// Where the value of a configuration key comes from, in increasing priority.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "file",
+            ConfigSource::Env => "env",
+            ConfigSource::Cli => "cli",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A configuration tree built by merging layers over each other, remembering
+/// the layer every leaf comes from. Keys are dotted paths like `http.dist_path`.
+#[derive(Default)]
+pub struct LayeredConfig {
+    pub value: Value,
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+impl LayeredConfig {
+    /// Deep merges `layer` over the current tree. Objects are merged key by
+    /// key, any other value, arrays included, replaces the previous one. An
+    /// object keyed by indexes merges into the elements of an array instead,
+    /// an index one past the end appending an element.
+    pub fn merge(&mut self, layer: Value, source: ConfigSource) {
+        let mut value = std::mem::take(&mut self.value);
+        merge_value(&mut value, layer, source, "", &mut self.sources);
+        self.value = value;
+    }
+
+    /// Sets the key at a dotted `path` from its textual form, as found in an
+    /// environment variable or a command-line flag. Array elements are named
+    /// by their index, e.g. `proxy.routes.0.forward_to`.
+    pub fn set(&mut self, path: &str, raw: &str, source: ConfigSource) {
+        let existing = path.split('.')
+            .try_fold(&self.value, |value, key| match value {
+                Value::Array(elements) => elements.get(key.parse::<usize>().ok()?),
+                value => value.get(key),
+            });
+        let value = parse_value(raw, existing);
+        let layer = path.rsplit('.')
+            .fold(value, |value, key| Value::Object(Map::from_iter([(key.to_string(), value)])));
+        self.merge(layer, source);
+    }
+
+    /// Applies every `SERVER__SECTION__KEY` variable, e.g.
+    /// `SERVER__HTTP__DIST_PATH` sets `http.dist_path`.
+    pub fn merge_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) {
+        for (name, raw) in vars {
+            let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let path = path.split(ENV_SEPARATOR)
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+                .join(".");
+            self.set(&path, &raw, ConfigSource::Env);
+        }
+    }
+}
+
+/// Deep merges `layer` over `base`, see [`LayeredConfig::merge`].
+pub fn merged(base: Value, layer: Value) -> Value {
+    let mut layered = LayeredConfig { value: base, sources: BTreeMap::new() };
+    layered.merge(layer, ConfigSource::File);
+    layered.value
+}
+
+/// Name of the environment variable setting a dotted `key`.
+pub fn env_var_name(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.replace('.', ENV_SEPARATOR).to_uppercase())
+}
+
+fn merge_value(
+    base: &mut Value,
+    layer: Value,
+    source: ConfigSource,
+    path: &str,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                let path = join_path(path, &key);
+                merge_value(base.entry(key).or_insert(Value::Null), value, source, &path, sources);
+            }
+        }
+        (Value::Array(base), Value::Object(layer)) if is_indexed(base, &layer) => {
+            let mut layer: Vec<(usize, Value)> = layer.into_iter()
+                .map(|(key, value)| (key.parse().unwrap(), value))
+                .collect();
+            layer.sort_by_key(|(index, _)| *index);
+            for (index, value) in layer {
+                if index == base.len() {
+                    base.push(Value::Null);
+                }
+                merge_value(&mut base[index], value, source, &join_path(path, &index.to_string()), sources);
+            }
+        }
+        (base, layer) => {
+            // the replaced subtree no longer comes from the previous layers
+            sources.retain(|key, _| key != path && !key.starts_with(&format!("{path}.")));
+            record_sources(&layer, source, path, sources);
+            *base = layer;
+        }
+    }
+}
+
+/// Whether every key of `layer` is the index of an element of `base`, or the
+/// one following its end.
+fn is_indexed(base: &[Value], layer: &Map<String, Value>) -> bool {
+    !layer.is_empty() && layer.keys().all(|key| key.parse::<usize>().is_ok_and(|index| index <= base.len()))
+}
+
+fn record_sources(value: &Value, source: ConfigSource, path: &str, sources: &mut BTreeMap<String, ConfigSource>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                record_sources(value, source, &join_path(path, key), sources);
+            }
+        }
+        _ => {
+            sources.insert(path.to_string(), source);
+        }
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Reads `raw` as JSON (numbers, booleans, arrays...) unless the key already
+/// holds a string, so that `SERVER__HOST=1234` stays a string.
+fn parse_value(raw: &str, existing: Option<&Value>) -> Value {
+    if let Some(Value::String(_)) = existing {
+        return Value::String(raw.to_string());
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn routes() -> LayeredConfig {
+        let mut layered = LayeredConfig::default();
+        layered.merge(json!({"proxy": {"routes": [{"prefix": "/a", "forward_to": "http://a"}]}}), ConfigSource::File);
+        layered
+    }
+
+    #[test]
+    fn sets_array_elements_by_index() {
+        let mut layered = routes();
+        layered.merge_env([("SERVER__PROXY__ROUTES__0__FORWARD_TO".to_string(), "http://b:9".to_string())]);
+        assert_eq!(layered.value, json!({"proxy": {"routes": [{"prefix": "/a", "forward_to": "http://b:9"}]}}));
+        assert_eq!(layered.sources["proxy.routes"], ConfigSource::File);
+        assert_eq!(layered.sources["proxy.routes.0.forward_to"], ConfigSource::Env);
+    }
+
+    #[test]
+    fn appends_one_past_the_end() {
+        let mut layered = routes();
+        layered.set("proxy.routes.1.prefix", "/b", ConfigSource::Cli);
+        assert_eq!(layered.value["proxy"]["routes"][1], json!({"prefix": "/b"}));
+    }
+
+    #[test]
+    fn replaces_whole_arrays() {
+        let mut layered = routes();
+        layered.set("proxy.routes", "[]", ConfigSource::Cli);
+        assert_eq!(layered.value["proxy"]["routes"], json!([]));
+        assert_eq!(layered.sources["proxy.routes"], ConfigSource::Cli);
+    }
+}
@@ -1,29 +1,223 @@
 pub mod server_config;
+mod layered;
+mod validate;
 
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Context, Result};
+use arc_swap::ArcSwap;
 use server_config::ServerConfig;
-use lazy_static::lazy_static;
+use layered::LayeredConfig;
+
+pub use layered::ConfigSource;
 
 const DEFAULT_CONFIG: &str = include_str!("../../server.json");
+const DEFAULT_CONFIG_PATH: &str = "server.toml";
+
+/// The configuration the server currently runs with, swapped on reload.
+pub type SharedConfig = Arc<ArcSwap<ServerConfig>>;
+
+/// Values given on the command line, applied over the configuration file and
+/// the environment.
+#[derive(Default, Clone)]
+pub struct ConfigOverrides {
+    /// Configuration file given explicitly, which must then exist.
+    pub path: Option<PathBuf>,
+    /// Dotted keys such as `http.dist_path` with their textual value.
+    pub values: Vec<(String, String)>,
+}
+
+/// The effective configuration along with the layer each key comes from.
+pub struct ResolvedConfig {
+    pub config: ServerConfig,
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// The configuration file which was read, if any.
+    pub file: Option<PathBuf>,
+    /// Problems which do not prevent the server from running, such as unknown keys.
+    pub warnings: Vec<String>,
+}
+
+impl ConfigOverrides {
+    /// The configuration file to read, `server.toml` unless given explicitly.
+    pub fn config_path(&self) -> &Path {
+        self.path.as_deref().unwrap_or(Path::new(DEFAULT_CONFIG_PATH))
+    }
+}
+
+pub fn default_config() -> ServerConfig {
+    serde_json::from_str(DEFAULT_CONFIG).unwrap()
+}
+
+/// Fills the sections and keys missing from `value` with the defaults.
+fn with_defaults(value: serde_json::Value) -> serde_json::Value {
+    layered::merged(serde_json::from_str(DEFAULT_CONFIG).unwrap(), value)
+}
+
+/// Writes the default configuration to `path` for the `init` command.
+pub fn write_default_config(path: Option<&Path>, force: bool) -> Result<PathBuf> {
+    let path = path.unwrap_or(Path::new(DEFAULT_CONFIG_PATH));
+    if path.exists() && !force {
+        bail!("{} already exists, use --force to overwrite it", path.display());
+    }
+    std::fs::write(path, toml::to_string(&default_config())?)
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(path.to_path_buf())
+}
+
+/// Resolves the configuration from, in increasing priority, the embedded
+/// defaults, the configuration file, `SERVER__*` environment variables and
+/// the command line.
+pub fn resolve_config(overrides: &ConfigOverrides) -> Result<ResolvedConfig> {
+    let mut layered = LayeredConfig::default();
+    layered.merge(serde_json::from_str(DEFAULT_CONFIG)?, ConfigSource::Default);
 
-lazy_static! {
-    pub static ref SERVER_CONFIG: ServerConfig = {
-        let default = serde_json::from_str(DEFAULT_CONFIG).unwrap();
-        load_or_create_config("server.toml", default)
+    let path = overrides.config_path();
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => Some(data),
+        // without an explicit path the defaults are enough to run
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && overrides.path.is_none() => None,
+        Err(err) => {
+            return Err(anyhow!(err)).with_context(|| {
+                format!("Could not read {}, run `server init` to create it", path.display())
+            });
+        }
+    };
+    if let Some(data) = data.as_deref() {
+        let file: serde_json::Value = toml::from_str(data)
+            .map_err(|err| anyhow!("{}: {err}", path.display()))?;
+        layered.merge(file, ConfigSource::File);
+    }
+
+    // variables which are not valid UTF-8 cannot name a key, nor hold a value
+    layered.merge_env(std::env::vars_os().filter_map(|(name, raw)| {
+        Some((name.into_string().ok()?, raw.into_string().ok()?))
+    }));
+    for (key, raw) in overrides.values.iter() {
+        layered.set(key, raw, ConfigSource::Cli);
+    }
+
+    let legacy_proxy = map_legacy_proxy(&mut layered)?;
+
+    let sources = layered.sources;
+    let origin = |key: &str| {
+        // array elements are written `routes[0]` in error paths
+        let key = key.replace('[', ".").replace(']', "");
+        match source_of(&sources, &key) {
+            Some(ConfigSource::File) => match data.as_deref().and_then(|data| locate(data, &key)) {
+                Some((line, column)) => format!("{}:{line}:{column}: ", path.display()),
+                None => format!("{}: ", path.display()),
+            },
+            Some(ConfigSource::Env) => format!("environment variable {}: ", layered::env_var_name(&key)),
+            Some(ConfigSource::Cli) => "command line: ".to_string(),
+            _ => String::new(),
+        }
     };
+
+    let mut unknown_keys = Vec::new();
+    let mut track = serde_path_to_error::Track::new();
+    let mut on_unknown_key = |key: serde_ignored::Path| unknown_keys.push(key.to_string());
+    let deserializer = serde_ignored::Deserializer::new(layered.value, &mut on_unknown_key);
+    let deserializer = serde_path_to_error::Deserializer::new(deserializer, &mut track);
+    // the tree already holds the defaults, skip merging them again
+    let config = ServerConfig::deserialize(deserializer).map_err(|err| {
+        let key = track.path().to_string();
+        anyhow!("{}{key}: {err}", origin(&key))
+    })?;
+    let mut warnings: Vec<String> = unknown_keys.iter()
+        .map(|key| format!("{}unknown key {key} is ignored", origin(key)))
+        .collect();
+    if legacy_proxy {
+        warnings.push(format!(
+            "{}proxy.base_path and proxy.forward_to are deprecated, they are read as a single [[proxy.routes]] entry",
+            origin("proxy.routes"),
+        ));
+    }
+
+    let problems = validate::validate(&config);
+    if !problems.is_empty() {
+        bail!("Invalid configuration:\n  - {}", problems.join("\n  - "));
+    }
+
+    Ok(ResolvedConfig {
+        config,
+        sources,
+        file: data.map(|_| path.to_path_buf()),
+        warnings,
+    })
 }
 
-fn load_or_create_config(path: &str, default: ServerConfig) -> ServerConfig {
-    std::fs::read_to_string(path).map_or_else(
-        | _ | {
-            std::fs::write(path, toml::to_string(&default).unwrap()).unwrap();
-            default
-        },
-        | data | {
-            toml::from_str(&data.as_str()).unwrap()
+/// Maps `proxy.base_path` and `proxy.forward_to`, which configured the only
+/// proxy route before `[[proxy.routes]]`, onto a route replacing the default
+/// ones. A missing half keeps the value of the default route. Returns whether
+/// they were found.
+fn map_legacy_proxy(layered: &mut LayeredConfig) -> Result<bool> {
+    let Some(proxy) = layered.value.get_mut("proxy").and_then(serde_json::Value::as_object_mut) else {
+        return Ok(false);
+    };
+    let base_path = proxy.remove("base_path");
+    let forward_to = proxy.remove("forward_to");
+    if base_path.is_none() && forward_to.is_none() {
+        return Ok(false);
+    }
+    let routes_set = layered.sources.iter()
+        .any(|(key, source)| key.starts_with("proxy.routes") && *source != ConfigSource::Default);
+    if routes_set {
+        bail!("proxy.base_path and proxy.forward_to cannot be combined with [[proxy.routes]], move them to a route");
+    }
+    let source = ["proxy.base_path", "proxy.forward_to"].iter()
+        .filter_map(|key| layered.sources.remove(*key))
+        .max()
+        .unwrap_or(ConfigSource::File);
+
+    let default_route = proxy.get("routes").and_then(|routes| routes.get(0));
+    let default_key = |key: &str| default_route.and_then(|route| route.get(key)).cloned().unwrap_or_default();
+    let route = serde_json::json!({
+        "prefix": base_path.unwrap_or_else(|| default_key("prefix")),
+        "forward_to": forward_to.unwrap_or_else(|| default_key("forward_to")),
+    });
+    layered.merge(serde_json::json!({ "proxy": { "routes": [route] } }), source);
+    Ok(true)
+}
+
+/// The layer a key comes from. Sources are only recorded for leaves, so a
+/// section, e.g. one missing a field, comes from the layer of its nearest
+/// recorded ancestor or else from the highest priority layer among its keys.
+fn source_of(sources: &BTreeMap<String, ConfigSource>, key: &str) -> Option<ConfigSource> {
+    let mut ancestor = Some(key);
+    while let Some(current) = ancestor {
+        if let Some(source) = sources.get(current) {
+            return Some(*source);
         }
-    )
+        ancestor = current.rsplit_once('.').map(|(parent, _)| parent);
+    }
+    let prefix = format!("{key}.");
+    sources.range(prefix.clone()..)
+        .take_while(|(descendant, _)| descendant.starts_with(&prefix))
+        .map(|(_, source)| *source)
+        .max()
+}
+
+/// Finds the line and column, both starting at 1, of the value of a dotted
+/// `key` in a TOML document, or of its nearest ancestor found in it.
+fn locate(data: &str, key: &str) -> Option<(usize, usize)> {
+    let document = toml_edit::ImDocument::parse(data).ok()?;
+    let segments: Vec<&str> = key.split('.').collect();
+    let offset = (1..=segments.len()).rev()
+        .find_map(|len| span_start(document.as_item(), &segments[..len]))?;
+    let before = &data[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+    Some((line, column))
 }
 
-pub fn init_config() {
-    let _config = &*SERVER_CONFIG;
+fn span_start(item: &toml_edit::Item, segments: &[&str]) -> Option<usize> {
+    let mut item = item;
+    for segment in segments {
+        item = match segment.parse::<usize>() {
+            Ok(index) => item.get(index)?,
+            Err(_) => item.get(segment)?,
+        };
+    }
+    Some(item.span()?.start)
 }
@@ -1,27 +1,285 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    #[default]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// The section is optional as a whole, its keys default to the values below
+/// when it is present.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TlsConfig {
+    #[serde(default = "default_tls_cert_path")]
+    pub cert_path: String,
+    #[serde(default = "default_tls_key_path")]
+    pub key_path: String,
+    #[serde(default)]
+    pub min_version: TlsVersion,
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+    #[serde(default)]
+    pub redirect_http_port: Option<u16>,
+}
+
+fn default_tls_cert_path() -> String {
+    "cert.pem".to_string()
+}
+
+fn default_tls_key_path() -> String {
+    "key.pem".to_string()
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    60
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    Pretty,
+    Json,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+/// Optional as a whole like [`TlsConfig`].
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LogFileConfig {
+    #[serde(default = "default_log_directory")]
+    pub directory: String,
+    #[serde(default = "default_log_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub rotation: LogRotation,
+}
+
+fn default_log_directory() -> String {
+    "logs".to_string()
+}
+
+fn default_log_prefix() -> String {
+    "server.log".to_string()
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub filter: String,
+    pub file: Option<LogFileConfig>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct HTTPConfig {
     pub base_path: String,
     pub dist_path: String,
+    pub cache_max_age_secs: u64,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct WebsocketConfig {
     pub base_path: String,
+    pub resume_grace_secs: u64,
+    pub resume_buffer_size: usize,
+    pub allowed_origins: Vec<String>,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ProxyConfig {
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    None,
+    Static,
+    Hmac,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct StaticToken {
+    pub token: String,
+    pub identity: String,
+    pub roles: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AuthConfig {
+    pub mode: AuthMode,
+    pub cookie_name: String,
+    pub hmac_secret: String,
+    pub tokens: Vec<StaticToken>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AuthorizationConfig {
+    pub enabled: bool,
+    pub default_roles: Vec<String>,
+    pub roles: BTreeMap<String, Vec<u16>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: u32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CommandRateLimit {
+    pub cmd_id: u16,
+    pub rate: f64,
+    pub burst: u32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub disconnect_after: u32,
+    pub session: RateLimit,
+    pub ip: RateLimit,
+    pub commands: Vec<CommandRateLimit>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ShutdownConfig {
+    pub drain_timeout_secs: u64,
+    pub reconnect_after_secs: u32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AdminConfig {
+    pub enabled: bool,
     pub base_path: String,
+    pub token: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub path: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct HealthConfig {
+    pub liveness_path: String,
+    pub readiness_path: String,
+    pub upstream_timeout_ms: u64,
+}
+
+/// How the matched prefix of a proxied path is rewritten before forwarding.
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PathRewrite {
+    /// `/api/users` on prefix `/api` is forwarded as `/users`
+    #[default]
+    Strip,
+    /// The path is forwarded unchanged
+    Keep,
+    /// The prefix is replaced, e.g. with `/v2` `/api/users` becomes `/v2/users`
+    Replace(String),
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct HeaderRules {
+    /// Headers added, or replaced when already present
+    #[serde(default)]
+    pub set: BTreeMap<String, String>,
+    /// Headers removed
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Only `prefix` and `forward_to` are required, like the keys of the optional
+/// sections the other keys default to the values below, which are not repeated
+/// in `server.json`.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProxyRoute {
+    pub prefix: String,
     pub forward_to: String,
+    #[serde(default)]
+    pub rewrite: PathRewrite,
+    /// 0 waits for the upstream indefinitely
+    #[serde(default = "default_proxy_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Largest request body forwarded in bytes, 0 for no limit
+    #[serde(default = "default_proxy_max_body_size")]
+    pub max_body_size: u64,
+    /// Forwards the `Host` header of the client instead of the upstream's
+    #[serde(default)]
+    pub preserve_host: bool,
+    /// Sets `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host`
+    #[serde(default = "default_true")]
+    pub x_forwarded: bool,
+    /// Sets the `Forwarded` header of RFC 7239
+    #[serde(default)]
+    pub forwarded: bool,
+    #[serde(default)]
+    pub request_headers: HeaderRules,
+    #[serde(default)]
+    pub response_headers: HeaderRules,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_proxy_timeout_ms() -> u64 {
+    30000
+}
+
+fn default_proxy_max_body_size() -> u64 {
+    10 * 1024 * 1024
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProxyConfig {
+    /// Keeps the forwarding headers sent by the client, only to be set behind
+    /// another proxy which sets them
+    pub trust_forwarded: bool,
+    pub routes: Vec<ProxyRoute>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(remote = "Self")]
 pub struct ServerConfig {
     pub host: String,
     pub port: u32,
+    pub listen: Vec<String>,
+    pub reload_interval_secs: u64,
+    pub tls: Option<TlsConfig>,
+    pub log: LogConfig,
     pub http: HTTPConfig,
     pub websocket: WebsocketConfig,
+    pub auth: AuthConfig,
+    pub authorization: AuthorizationConfig,
+    pub rate_limit: RateLimitConfig,
+    pub shutdown: ShutdownConfig,
+    pub admin: AdminConfig,
+    pub metrics: MetricsConfig,
+    pub health: HealthConfig,
     pub proxy: ProxyConfig,
 }
+
+/// Missing sections and keys take their value from the embedded `server.json`,
+/// so configuration files only need to hold what they change.
+impl<'de> Deserialize<'de> for ServerConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        ServerConfig::deserialize(super::with_defaults(value)).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for ServerConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ServerConfig::serialize(self, serializer)
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::path::Path;
+use axum::http::{HeaderName, HeaderValue, Uri};
+use tracing_subscriber::EnvFilter;
+use super::server_config::{AuthMode, HeaderRules, PathRewrite, RateLimit, ServerConfig};
+
+/// Checks the constraints serde can not express and returns every problem
+/// found, so that they can all be fixed at once.
+pub fn validate(config: &ServerConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if config.port == 0 || config.port > u16::MAX as u32 {
+        problems.push(format!("port: {} is not between 1 and 65535", config.port));
+    }
+    for address in config.listen.iter() {
+        if let Some(path) = address.strip_prefix("unix:") {
+            if !cfg!(unix) {
+                problems.push(format!("listen: {address} is a Unix domain socket, which this platform does not support"));
+            } else if path.is_empty() {
+                problems.push(format!("listen: {address} has no socket path"));
+            }
+        } else if address.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok()).is_none() {
+            problems.push(format!("listen: {address} is neither host:port nor unix:<path>"));
+        }
+    }
+
+    let mut base_paths = vec![
+        ("http.base_path", &config.http.base_path),
+        ("websocket.base_path", &config.websocket.base_path),
+        ("health.liveness_path", &config.health.liveness_path),
+        ("health.readiness_path", &config.health.readiness_path),
+    ];
+    if config.admin.enabled {
+        base_paths.push(("admin.base_path", &config.admin.base_path));
+    }
+    if config.metrics.enabled {
+        base_paths.push(("metrics.path", &config.metrics.path));
+    }
+    for (key, path) in base_paths.iter() {
+        if !path.starts_with('/') {
+            problems.push(format!("{key}: \"{path}\" does not start with /"));
+        }
+    }
+    if config.admin.enabled && config.admin.base_path.trim_end_matches('/').is_empty() {
+        problems.push("admin.base_path: must not be /".to_string());
+    }
+
+    // every path is routed on the same router, which can not be built with
+    // two routes on the same path
+    let mut routes: Vec<(String, &str)> = base_paths.iter()
+        .map(|(key, path)| (key.to_string(), path.as_str()))
+        .collect();
+    routes.extend(config.proxy.routes.iter().enumerate()
+        .map(|(index, route)| (format!("proxy.routes.{index}.prefix"), route.prefix.as_str())));
+    let mut paths: HashMap<&str, &str> = HashMap::new();
+    for (key, path) in routes.iter() {
+        let normalized = match path.trim_end_matches('/') {
+            "" => "/",
+            trimmed => trimmed,
+        };
+        match paths.get(normalized) {
+            Some(other) => problems.push(format!("{key}: \"{path}\" is already used by {other}")),
+            None => { paths.insert(normalized, key); }
+        }
+    }
+
+    for (index, route) in config.proxy.routes.iter().enumerate() {
+        let key = format!("proxy.routes.{index}");
+        if !route.prefix.starts_with('/') || route.prefix.trim_end_matches('/').is_empty() {
+            problems.push(format!("{key}.prefix: \"{}\" does not start with / or is /", route.prefix));
+        }
+        match route.forward_to.parse::<Uri>() {
+            Ok(uri) if uri.scheme_str() == Some("http") && uri.authority().is_some() => {}
+            // the proxy client has no TLS connector
+            Ok(uri) if uri.scheme_str() == Some("https") => {
+                problems.push(format!("{key}.forward_to: \"{}\" is https, only http upstreams are supported", route.forward_to))
+            }
+            _ => problems.push(format!("{key}.forward_to: \"{}\" is not an http URI", route.forward_to)),
+        }
+        if let PathRewrite::Replace(replacement) = &route.rewrite {
+            if !replacement.starts_with('/') {
+                problems.push(format!("{key}.rewrite: \"{replacement}\" does not start with /"));
+            }
+        }
+        check_header_rules(&mut problems, &format!("{key}.request_headers"), &route.request_headers);
+        check_header_rules(&mut problems, &format!("{key}.response_headers"), &route.response_headers);
+    }
+
+    if !Path::new(&config.http.dist_path).is_dir() {
+        problems.push(format!("http.dist_path: directory \"{}\" does not exist", config.http.dist_path));
+    }
+
+    if let Some(tls) = &config.tls {
+        for (key, path) in [("tls.cert_path", &tls.cert_path), ("tls.key_path", &tls.key_path)] {
+            if !Path::new(path).is_file() {
+                problems.push(format!("{key}: file \"{path}\" does not exist"));
+            }
+        }
+    }
+
+    match config.auth.mode {
+        AuthMode::Hmac if config.auth.hmac_secret.is_empty() => {
+            problems.push("auth.hmac_secret: must be set when auth.mode is \"hmac\"".to_string());
+        }
+        AuthMode::Static if config.auth.tokens.is_empty() => {
+            problems.push("auth.tokens: must not be empty when auth.mode is \"static\"".to_string());
+        }
+        _ => {}
+    }
+
+    if config.rate_limit.enabled {
+        check_rate_limit(&mut problems, "rate_limit.session", &config.rate_limit.session);
+        check_rate_limit(&mut problems, "rate_limit.ip", &config.rate_limit.ip);
+        for command in config.rate_limit.commands.iter() {
+            let limit = RateLimit { rate: command.rate, burst: command.burst };
+            check_rate_limit(&mut problems, &format!("rate_limit.commands.{}", command.cmd_id), &limit);
+        }
+    }
+
+    if let Err(err) = EnvFilter::try_new(&config.log.filter) {
+        problems.push(format!("log.filter: {err}"));
+    }
+
+    problems
+}
+
+fn check_header_rules(problems: &mut Vec<String>, key: &str, rules: &HeaderRules) {
+    for name in rules.remove.iter() {
+        if HeaderName::try_from(name).is_err() {
+            problems.push(format!("{key}.remove: \"{name}\" is not a valid header name"));
+        }
+    }
+    for (name, value) in rules.set.iter() {
+        if HeaderName::try_from(name).is_err() {
+            problems.push(format!("{key}.set: \"{name}\" is not a valid header name"));
+        }
+        if HeaderValue::try_from(value).is_err() {
+            problems.push(format!("{key}.set.{name}: \"{value}\" is not a valid header value"));
+        }
+    }
+}
+
+fn check_rate_limit(problems: &mut Vec<String>, key: &str, limit: &RateLimit) {
+    if limit.rate.is_nan() || limit.rate <= 0.0 {
+        problems.push(format!("{key}.rate: must be greater than 0"));
+    }
+    if limit.burst == 0 {
+        problems.push(format!("{key}.burst: must be at least 1"));
+    }
+}
@@ -0,0 +1,87 @@
+pub mod auth;
+pub mod config;
+pub mod util;
+pub mod services;
+pub mod net;
+pub mod tls;
+pub mod listener;
+pub mod metrics;
+pub mod reload;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use arc_swap::ArcSwap;
+use axum::Router;
+use axum::body::Body;
+use tokio_util::sync::CancellationToken;
+use auth::{Authenticator, Policy};
+use config::SharedConfig;
+use config::server_config::ServerConfig;
+use hyper_util::{client::legacy::connect::HttpConnector, rt::TokioExecutor};
+use metrics::Metrics;
+use net::rate_limit::RateLimiter;
+use net::session_manager::SessionManager;
+use net::shared_stream::SharedStreams;
+
+pub type HttpClient = hyper_util::client::legacy::Client<HttpConnector, Body>;
+
+#[derive(Clone)]
+pub struct ServerContext {
+    pub config: SharedConfig,
+    pub http_client: HttpClient,
+    pub authenticator: Arc<dyn Authenticator>,
+    pub policy: Arc<Policy>,
+    pub rate_limiter: RateLimiter,
+    pub metrics: Arc<Metrics>,
+    pub sessions: SessionManager,
+    pub shared_streams: SharedStreams,
+    pub shutdown: CancellationToken,
+    pub listening: Arc<AtomicBool>,
+    // database here
+}
+
+impl ServerContext {
+    pub fn new(config: ServerConfig) -> Self {
+        let http_client: HttpClient =
+            hyper_util::client::legacy::Client::<(), ()>::builder(TokioExecutor::new())
+                .build(HttpConnector::new());
+
+        Self {
+            authenticator: auth::create_authenticator(&config.auth),
+            policy: Arc::new(Policy::new(&config.authorization)),
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            http_client,
+            rate_limiter: RateLimiter::default(),
+            metrics: Arc::new(Metrics::new().expect("Failed to register the metrics")),
+            sessions: SessionManager::default(),
+            shared_streams: SharedStreams::default(),
+            shutdown: CancellationToken::new(),
+            listening: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Builds the application serving `config` with a context of its own. The
+/// caller binds the listeners, so the application reports itself ready as
+/// soon as it is served.
+pub fn build_app(config: ServerConfig) -> Router {
+    let context = ServerContext::new(config);
+    context.listening.store(true, Ordering::Relaxed);
+    router(context)
+}
+
+/// Builds the application on an existing context, the routes are laid out
+/// from the configuration of the context at the time of the call. It is ready
+/// once `listening` is set, and cancelling `shutdown` stops its streams and
+/// reports it as shutting down.
+pub fn router(context: ServerContext) -> Router {
+    let config = context.config.load_full();
+    let mut router = Router::new();
+    router = services::websocket::setup_routes(router, &config);
+    router = services::admin::setup_routes(router, &config);
+    router = services::metrics::setup_routes(router, &config);
+    router = services::health::setup_routes(router, &config);
+    router = services::reverse_proxy::setup_routes(router, &config);
+    router = services::web::setup_routes(router, &config);
+    router.with_state(context)
+}
@@ -0,0 +1,191 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+#[cfg(unix)]
+use std::os::unix::{fs::FileTypeExt, net::UnixListener as StdUnixListener};
+use anyhow::{Context, Result};
+use axum::{extract::ConnectInfo, Extension, Router};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+#[cfg(unix)]
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto::Builder, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use listenfd::ListenFd;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio_util::sync::CancellationToken;
+use crate::config::server_config::ServerConfig;
+
+const UNIX_PREFIX: &str = "unix:";
+
+pub enum Listener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(StdUnixListener),
+}
+
+impl Listener {
+    pub fn describe(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr()
+                .map_or_else(|_| "tcp socket".to_string(), |addr| addr.to_string()),
+            #[cfg(unix)]
+            Listener::Unix(listener) => listener.local_addr().ok()
+                .and_then(|addr| addr.as_pathname().map(|path| format!("{UNIX_PREFIX}{}", path.display())))
+                .unwrap_or_else(|| "unix socket".to_string()),
+        }
+    }
+}
+
+/// Opens the listeners the server accepts connections on: the sockets
+/// inherited through systemd socket activation if any, otherwise the `listen`
+/// addresses, falling back to `host:port`.
+pub fn bind_listeners(config: &ServerConfig) -> Result<Vec<Listener>> {
+    let mut listen_fd = ListenFd::from_env();
+    if listen_fd.len() > 0 {
+        return (0..listen_fd.len())
+            .filter_map(|index| take_inherited(&mut listen_fd, index).transpose())
+            .collect();
+    }
+
+    let addresses = if config.listen.is_empty() {
+        vec![format!("{}:{}", config.host, config.port)]
+    } else {
+        config.listen.clone()
+    };
+    addresses.iter().map(|address| bind(address)).collect()
+}
+
+fn take_inherited(listen_fd: &mut ListenFd, index: usize) -> Result<Option<Listener>> {
+    if let Ok(Some(listener)) = listen_fd.take_tcp_listener(index) {
+        listener.set_nonblocking(true)?;
+        return Ok(Some(Listener::Tcp(listener)));
+    }
+    take_inherited_unix(listen_fd, index)
+}
+
+#[cfg(unix)]
+fn take_inherited_unix(listen_fd: &mut ListenFd, index: usize) -> Result<Option<Listener>> {
+    let listener = listen_fd.take_unix_listener(index)
+        .with_context(|| format!("Inherited file descriptor {index} is not a stream socket"))?;
+    listener.map(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Unix(listener))
+    }).transpose()
+}
+
+#[cfg(not(unix))]
+fn take_inherited_unix(_listen_fd: &mut ListenFd, index: usize) -> Result<Option<Listener>> {
+    anyhow::bail!("Inherited file descriptor {index} is not a TCP socket")
+}
+
+fn bind(address: &str) -> Result<Listener> {
+    if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+        return bind_unix(address, path);
+    }
+    let listener = std::net::TcpListener::bind(address)
+        .with_context(|| format!("Could not listen at {address}"))?;
+    listener.set_nonblocking(true)?;
+    Ok(Listener::Tcp(listener))
+}
+
+#[cfg(unix)]
+fn bind_unix(address: &str, path: &str) -> Result<Listener> {
+    // a socket file left behind by a previous run would make bind fail, any
+    // other file is most likely a mistake in the configuration and is kept
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            std::fs::remove_file(path).with_context(|| format!("Could not remove stale socket {path}"))?;
+        }
+        Ok(_) => anyhow::bail!("Could not listen at {address}: {path} exists and is not a socket"),
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            return Err(err).with_context(|| format!("Could not inspect {path}"));
+        }
+        Err(_) => {}
+    }
+    let listener = StdUnixListener::bind(path)
+        .with_context(|| format!("Could not listen at {address}"))?;
+    listener.set_nonblocking(true)?;
+    Ok(Listener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(address: &str, _path: &str) -> Result<Listener> {
+    anyhow::bail!("Could not listen at {address}: Unix domain sockets are not supported on this platform")
+}
+
+/// Scheme of the connection a request came in on, `https` behind TLS.
+#[derive(Clone, Copy)]
+pub struct Scheme(pub &'static str);
+
+/// Address of the peer of a request, unspecified when the application is
+/// served without `into_make_service_with_connect_info`, e.g. in tests.
+pub fn peer_addr(connect_info: Option<ConnectInfo<SocketAddr>>) -> SocketAddr {
+    connect_info.map_or(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)), |ConnectInfo(peer)| peer)
+}
+
+/// Serves `app` on a listener until the server handle or the shutdown token
+/// asks it to stop. TCP listeners use TLS when `rustls` is set, Unix sockets,
+/// only available on Unix platforms, always serve plain HTTP.
+pub async fn serve(
+    listener: Listener,
+    app: Router,
+    rustls: Option<RustlsConfig>,
+    handle: Handle,
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    match listener {
+        Listener::Tcp(listener) => {
+            let scheme = Scheme(if rustls.is_some() { "https" } else { "http" });
+            let make_service = app.layer(Extension(scheme))
+                .into_make_service_with_connect_info::<SocketAddr>();
+            match rustls {
+                Some(rustls) => axum_server::from_tcp_rustls(listener, rustls)
+                    .handle(handle)
+                    .serve(make_service)
+                    .await,
+                None => axum_server::from_tcp(listener)
+                    .handle(handle)
+                    .serve(make_service)
+                    .await,
+            }
+        }
+        #[cfg(unix)]
+        Listener::Unix(listener) => serve_unix(UnixListener::from_std(listener)?, app, shutdown).await,
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(listener: UnixListener, app: Router, shutdown: CancellationToken) -> io::Result<()> {
+    // peers of a Unix socket are local, handlers still expect an IP address
+    let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+    let app = app.layer(Extension(ConnectInfo(peer))).layer(Extension(Scheme("http")));
+    let builder = Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!("Failed to accept Unix socket connection: {err}");
+                    continue;
+                }
+            },
+            _ = shutdown.cancelled() => break,
+        };
+        let service = TowerToHyperService::new(app.clone());
+        let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+        let connection = graceful.watch(connection.into_owned());
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::debug!("Unix socket connection ended with error: {err}");
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+    Ok(())
+}
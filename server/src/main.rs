@@ -1,55 +1,127 @@
-mod config;
-mod util;
-mod services;
-mod net;
+mod cli;
 
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 use anyhow::Result;
-use axum::{Router, ServiceExt};
-use axum::body::Body;
-use axum::extract::Request;
-use tokio::net::TcpListener;
+use axum_server::Handle;
+use clap::Parser;
+use tokio::task::JoinSet;
 use tracing::Level;
-use hyper_util::{client::legacy::connect::HttpConnector, rt::TokioExecutor};
+use cli::{Cli, Command};
+use server::{config, listener, reload, tls, util, ServerContext};
 
-type HttpClient = hyper_util::client::legacy::Client<HttpConnector, Body>;
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve);
+    match command {
+        Command::PrintDefaultConfig => {
+            print!("{}", toml::to_string(&config::default_config())?);
+            return Ok(());
+        }
+        Command::Init { force } => {
+            let path = config::write_default_config(cli.config.as_deref(), force)?;
+            println!("Wrote the default configuration to {}", path.display());
+            return Ok(());
+        }
+        _ => {}
+    }
 
-#[derive(Clone)]
-pub struct ServerContext {
-    pub http_client: HttpClient,
-    // database here
+    let overrides = cli.overrides();
+    let resolved = config::resolve_config(&overrides)?;
+    if let Command::CheckConfig { sources } = command {
+        for warning in resolved.warnings.iter() {
+            eprintln!("warning: {warning}");
+        }
+        if sources {
+            for (key, source) in resolved.sources.iter() {
+                println!("{key} = {source}");
+            }
+        } else {
+            print!("{}", toml::to_string(&resolved.config)?);
+        }
+        return Ok(());
+    }
+
+    serve(resolved, overrides).await
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    util::init_tracing();
-    config::init_config();
+async fn serve(resolved: config::ResolvedConfig, overrides: config::ConfigOverrides) -> Result<()> {
+    let (_log_guard, log_filter) = util::init_tracing(&resolved.config.log)?;
+    match &resolved.file {
+        Some(file) => tracing::info!("Loaded configuration from {}", file.display()),
+        None => tracing::info!("No configuration file found, using the defaults"),
+    }
+    for warning in resolved.warnings.iter() {
+        tracing::warn!("Configuration {warning}");
+    }
+    for (key, source) in resolved.sources.iter().filter(|(_, source)| **source != config::ConfigSource::Default) {
+        tracing::debug!("Configuration {key} is set by {source}");
+    }
 
     let span = tracing::span!(Level::DEBUG, "main");
     let _ = span.enter();
 
-    let app = create_router();
+    let config = &resolved.config;
+    let context = ServerContext::new(config.clone());
+    let app = server::router(context.clone());
+    reload::watch_config(context.clone(), overrides, log_filter);
 
-    let http_client: HttpClient =
-        hyper_util::client::legacy::Client::<(), ()>::builder(TokioExecutor::new())
-            .build(HttpConnector::new());
+    let rustls = match &config.tls {
+        Some(tls_config) => {
+            let rustls = tls::load_rustls_config(tls_config)?;
+            tls::watch_certificates(rustls.clone(), tls_config.clone(), context.shutdown.clone());
+            if let Some(port) = tls_config.redirect_http_port {
+                let addr = format!("{}:{port}", config.host);
+                let https_port = config.port;
+                let shutdown = context.shutdown.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = tls::redirect_http_to_https(addr, https_port, shutdown).await {
+                        tracing::error!("HTTP redirect listener failed: {err:#}");
+                    }
+                });
+            }
+            Some(rustls)
+        }
+        None => None,
+    };
 
-    let app = app.with_state(ServerContext {
-        http_client,
-    });
+    let handle = Handle::new();
+    let mut servers = JoinSet::new();
+    for listener in listener::bind_listeners(config)? {
+        let scheme = match (&listener, &rustls) {
+            (listener::Listener::Tcp(_), Some(_)) => "https",
+            _ => "http",
+        };
+        tracing::info!("Server is listening at {scheme}://{}", listener.describe());
+        servers.spawn(listener::serve(
+            listener, app.clone(), rustls.clone(), handle.clone(), context.shutdown.clone(),
+        ));
+    }
+    context.listening.store(true, Ordering::Relaxed);
 
-    let addr = format!("0.0.0.0:{}", config::SERVER_CONFIG.port);
-    let server = TcpListener::bind(&addr).await?;
+    tokio::select! {
+        Some(result) = servers.join_next() => return Ok(result??),
+        _ = util::shutdown_signal() => {}
+    }
 
-    tracing::info!("Server is listening at {addr}");
-    axum::serve(server, ServiceExt::<Request>::into_make_service(app)).await?;
+    tracing::info!("Shutting down, waiting for connections to drain");
+    let drain_timeout = Duration::from_secs(config.shutdown.drain_timeout_secs);
+    handle.graceful_shutdown(Some(drain_timeout));
+    let drain = async {
+        context.sessions.shutdown(&context.shutdown).await;
+        while let Some(result) = servers.join_next().await {
+            result??;
+        }
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(drain_timeout, drain).await {
+        Ok(result) => result?,
+        Err(_) => {
+            tracing::warn!("Drain timeout elapsed, closing remaining connections");
+            context.shutdown.cancel();
+        }
+    }
 
     Ok(())
 }
-
-fn create_router() -> Router<ServerContext> {
-    let mut router = Router::new();
-    router = services::websocket::setup_routes(router);
-    router = services::reverse_proxy::setup_routes(router);
-    router = services::web::setup_routes(router);
-    router
-}
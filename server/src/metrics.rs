@@ -0,0 +1,112 @@
+use std::sync::atomic::Ordering;
+use anyhow::Result;
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use crate::ServerContext;
+
+const NAMESPACE: &str = "fate_loom";
+
+/// Metrics of the server, exposed in the Prometheus text format.
+///
+/// Counters and histograms are updated where the events happen, gauges
+/// describing the session registry are computed when the metrics are scraped.
+pub struct Metrics {
+    registry: Registry,
+    sessions: IntGaugeVec,
+    tasks: IntGaugeVec,
+    /// Labels: `direction` (`in` or `out`), `cmd_id`.
+    pub packets: IntCounterVec,
+    /// Labels: `direction` (`in` or `out`).
+    pub bytes: IntCounterVec,
+    pub decode_errors: IntCounter,
+    /// Labels: `cmd_id`.
+    pub handler_duration: HistogramVec,
+    /// Labels: `route` (the prefix), `status`, the HTTP status code, `error`
+    /// if the upstream failed or `timeout`.
+    pub proxy_requests: IntCounterVec,
+    /// Labels: `route`.
+    pub proxy_duration: HistogramVec,
+    /// Labels: `result` (`hit` or `miss`).
+    pub static_files: IntCounterVec,
+    rate_limited: IntCounterVec,
+    rate_limit_disconnects: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)?;
+        let metrics = Self {
+            sessions: IntGaugeVec::new(
+                Opts::new("sessions", "Sessions by connection state"), &["state"])?,
+            tasks: IntGaugeVec::new(
+                Opts::new("streaming_tasks", "Running streaming tasks by kind"), &["kind"])?,
+            packets: IntCounterVec::new(
+                Opts::new("packets_total", "WebSocket messages by direction and command id"),
+                &["direction", "cmd_id"])?,
+            bytes: IntCounterVec::new(
+                Opts::new("bytes_total", "WebSocket payload bytes by direction"), &["direction"])?,
+            decode_errors: IntCounter::new(
+                "decode_errors_total", "Inbound messages which could not be decoded")?,
+            handler_duration: HistogramVec::new(
+                HistogramOpts::new("handler_duration_seconds", "Time spent handling a command"),
+                &["cmd_id"])?,
+            proxy_requests: IntCounterVec::new(
+                Opts::new("proxy_requests_total", "Proxied requests by route and upstream status"),
+                &["route", "status"])?,
+            proxy_duration: HistogramVec::new(
+                HistogramOpts::new("proxy_upstream_duration_seconds", "Latency of the proxy upstreams"),
+                &["route"])?,
+            static_files: IntCounterVec::new(
+                Opts::new("static_file_requests_total", "Static file requests by result"), &["result"])?,
+            rate_limited: IntCounterVec::new(
+                Opts::new("rate_limited_total", "Messages rejected by the rate limits by scope"), &["scope"])?,
+            rate_limit_disconnects: IntCounter::new(
+                "rate_limit_disconnects_total", "Sessions closed for exceeding the rate limits")?,
+            registry,
+        };
+
+        metrics.registry.register(Box::new(metrics.sessions.clone()))?;
+        metrics.registry.register(Box::new(metrics.tasks.clone()))?;
+        metrics.registry.register(Box::new(metrics.packets.clone()))?;
+        metrics.registry.register(Box::new(metrics.bytes.clone()))?;
+        metrics.registry.register(Box::new(metrics.decode_errors.clone()))?;
+        metrics.registry.register(Box::new(metrics.handler_duration.clone()))?;
+        metrics.registry.register(Box::new(metrics.proxy_requests.clone()))?;
+        metrics.registry.register(Box::new(metrics.proxy_duration.clone()))?;
+        metrics.registry.register(Box::new(metrics.static_files.clone()))?;
+        metrics.registry.register(Box::new(metrics.rate_limited.clone()))?;
+        metrics.registry.register(Box::new(metrics.rate_limit_disconnects.clone()))?;
+        Ok(metrics)
+    }
+
+    pub fn packet(&self, direction: &str, cmd_id: u16) {
+        self.packets.with_label_values(&[direction, &cmd_id.to_string()]).inc();
+    }
+
+    /// Refreshes the gauges and renders every metric.
+    pub async fn encode(&self, context: &ServerContext) -> Result<String> {
+        self.sessions.reset();
+        self.tasks.reset();
+        for session in context.sessions.list().await {
+            let state = if session.is_attached().await { "attached" } else { "detached" };
+            self.sessions.with_label_values(&[state]).inc();
+            for task in session.get_tasks().lock().await.values() {
+                self.tasks.with_label_values(&[task.kind.as_str()]).inc();
+            }
+        }
+
+        let stats = &context.rate_limiter.stats;
+        sync_counter(&self.rate_limited.with_label_values(&["session"]), stats.session_limited.load(Ordering::Relaxed));
+        sync_counter(&self.rate_limited.with_label_values(&["ip"]), stats.ip_limited.load(Ordering::Relaxed));
+        sync_counter(&self.rate_limit_disconnects, stats.disconnected.load(Ordering::Relaxed));
+
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+}
+
+/// Catches a counter up with a total kept outside of the registry.
+fn sync_counter(counter: &IntCounter, total: u64) {
+    counter.inc_by(total.saturating_sub(counter.get()));
+}
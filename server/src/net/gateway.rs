@@ -1,10 +1,57 @@
+use std::net::SocketAddr;
 use axum::{
     extract::ws::WebSocket
 };
+use proto::*;
+use tracing::Instrument;
+use crate::auth::Identity;
 use crate::ServerContext;
-use crate::net::session::Session;
+use crate::net::protocol::Protocol;
 
-pub async fn handle_socket(socket: WebSocket, _state: ServerContext) {
-    let mut session = Session::new(socket);
-    session.run().await;
+/// What was learned about the client while upgrading its connection.
+pub struct Handshake {
+    pub peer: SocketAddr,
+    pub subprotocol: Option<Protocol>,
+    pub identity: Identity,
+    pub resume_token: Option<String>,
+    /// Last sequence number received by a client resuming its session.
+    pub last_seq: Option<u64>,
+}
+
+pub async fn handle_socket(socket: WebSocket, state: ServerContext, handshake: Handshake) {
+    let sessions = state.sessions.clone();
+    let resumable = match handshake.resume_token.as_deref() {
+        Some(resume_token) => sessions.find_resumable(resume_token, &handshake.identity).await,
+        None => None,
+    };
+    let (mut session, epoch, resumed) = match resumable {
+        Some(mut session) => match session.attach(socket, handshake.subprotocol, handshake.last_seq).await {
+            Ok(epoch) => (session, epoch, true),
+            Err(err) => {
+                tracing::warn!("Failed to resume session {}: {err}", session.get_id());
+                return;
+            }
+        },
+        None => {
+            let session = sessions.create(socket, handshake, state).await;
+            let epoch = session.attach_epoch();
+            (session, epoch, false)
+        }
+    };
+
+    let notify = SessionInfoNotify {
+        session_id: session.get_id(),
+        resume_token: session.get_resume_token().to_string(),
+        resumed,
+        last_seq: session.last_seq().await,
+    };
+    let span = tracing::info_span!("session", id = session.get_id(), peer = %session.get_peer());
+    async {
+        tracing::debug!(resumed, "Session attached");
+        if session.send(cmd_id::SESSION_INFO_NOTIFY, notify).await.is_ok() {
+            session.run(epoch).await;
+        }
+        sessions.detach(&mut session, epoch).await;
+        tracing::debug!("Session detached");
+    }.instrument(span).await
 }
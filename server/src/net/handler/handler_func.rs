@@ -3,8 +3,13 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use anyhow::Result;
 use chrono::prelude::*;
+use prost::{Message, Name};
+use serde::Serialize;
 use rand::Rng;
-use crate::net::session::Session;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::Instrument;
+use crate::net::session::{Session, TaskHandle};
+use crate::net::shared_stream::{StreamKind, StreamParams, Subscription};
 use proto::*;
 
 pub async fn on_heartbeat_msg(
@@ -35,17 +40,64 @@ pub async fn on_random_number_request(
             ..Default::default()
         };
         session.send(cmd_id::RANDOM_NUMBER_RESPONSE, rsp).await
+    } else if !msg.shared_key.is_empty() {
+        let min = msg.min;
+        let max = msg.max;
+        let subscription = session.get_context().shared_streams.subscribe(
+            &msg.shared_key,
+            StreamKind::RandomNumber,
+            StreamParams { low: min, high: max, interval: msg.interval as u64 },
+            move || Some(rand::thread_rng().gen_range(min..=max)),
+        ).await;
+        match subscription {
+            Ok(subscription) => {
+                forward_shared_stream(
+                    session, id, subscription,
+                    TaskHandle::new(Arc::new(AtomicBool::new(true)), StreamKind::RandomNumber, msg),
+                    cmd_id::RANDOM_NUMBER_RESPONSE,
+                    | id, number, status | RandomNumberResponse {
+                        id,
+                        status,
+                        number,
+                    },
+                ).await;
+                Ok(())
+            }
+            Err(err) => {
+                let rsp = RandomNumberResponse {
+                    id: msg.id.clone(),
+                    status: Some(Status {
+                        code: StatusCode::InvalidRequest as i32,
+                        message: err.to_string(),
+                    }),
+                    ..Default::default()
+                };
+                session.send(cmd_id::RANDOM_NUMBER_RESPONSE, rsp).await
+            }
+        }
     } else {
         let task = Arc::new(AtomicBool::new(true));
-        session.add_task(&id, task.clone()).await;
+        session.add_task(&id, TaskHandle::new(task.clone(), StreamKind::RandomNumber, msg)).await;
         let interval = msg.interval as u64;
         let min = msg.min;
         let max = msg.max;
         let mut session = session.clone();
+        let shutdown = session.get_context().shutdown.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(interval));
             while task.load(Ordering::Relaxed) {
-                interval.tick().await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.cancelled() => {
+                        let rsp = RandomNumberResponse {
+                            id: id.clone(),
+                            status: Some(cancelled_status()),
+                            ..Default::default()
+                        };
+                        let _ = session.send(cmd_id::RANDOM_NUMBER_RESPONSE, rsp).await;
+                        break;
+                    }
+                }
                 let random_number = rand::thread_rng().gen_range(min..=max);
                 let rsp = RandomNumberResponse {
                     id: id.clone(),
@@ -56,7 +108,7 @@ pub async fn on_random_number_request(
                     .expect("Error in sending response");
             }
             session.remove_task(&id).await;
-        });
+        }.in_current_span());
         Ok(())
     }
 }
@@ -105,18 +157,71 @@ pub async fn on_incremental_sequence_request(
             ..Default::default()
         };
         session.send(cmd_id::INCREMENTAL_SEQUENCE_RESPONSE, rsp).await
+    } else if !msg.shared_key.is_empty() {
+        let mut num = msg.start;
+        let end = msg.end;
+        let subscription = session.get_context().shared_streams.subscribe(
+            &msg.shared_key,
+            StreamKind::IncrementalSequence,
+            StreamParams { low: msg.start, high: end, interval: msg.interval as u64 },
+            move || {
+                if num > end {
+                    return None;
+                }
+                num += 1;
+                Some(num - 1)
+            },
+        ).await;
+        match subscription {
+            Ok(subscription) => {
+                forward_shared_stream(
+                    session, id, subscription,
+                    TaskHandle::new(Arc::new(AtomicBool::new(true)), StreamKind::IncrementalSequence, msg),
+                    cmd_id::INCREMENTAL_SEQUENCE_RESPONSE,
+                    | id, number, status | IncrementalSequenceResponse {
+                        id,
+                        status,
+                        number,
+                    },
+                ).await;
+                Ok(())
+            }
+            Err(err) => {
+                let rsp = IncrementalSequenceResponse {
+                    id: msg.id.clone(),
+                    status: Some(Status {
+                        code: StatusCode::InvalidRequest as i32,
+                        message: err.to_string(),
+                    }),
+                    ..Default::default()
+                };
+                session.send(cmd_id::INCREMENTAL_SEQUENCE_RESPONSE, rsp).await
+            }
+        }
     } else {
         let task = Arc::new(AtomicBool::new(true));
-        session.add_task(&id, task.clone()).await;
+        session.add_task(&id, TaskHandle::new(task.clone(), StreamKind::IncrementalSequence, msg)).await;
         let interval = msg.interval as u64;
         let start = msg.start;
         let end = msg.end;
         let mut session = session.clone();
+        let shutdown = session.get_context().shutdown.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(interval));
             let mut num = start;
             while task.load(Ordering::Relaxed) && num <= end {
-                interval.tick().await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.cancelled() => {
+                        let rsp = IncrementalSequenceResponse {
+                            id: id.clone(),
+                            status: Some(cancelled_status()),
+                            ..Default::default()
+                        };
+                        let _ = session.send(cmd_id::INCREMENTAL_SEQUENCE_RESPONSE, rsp).await;
+                        break;
+                    }
+                }
                 let rsp = IncrementalSequenceResponse {
                     id: id.clone(),
                     number: num,
@@ -127,7 +232,7 @@ pub async fn on_incremental_sequence_request(
                 num += 1;
             }
             session.remove_task(&id).await;
-        });
+        }.in_current_span());
         Ok(())
     }
 }
@@ -161,6 +266,56 @@ pub async fn on_stop_incremental_sequence_request(
     }
 }
 
+fn cancelled_status() -> Status {
+    Status {
+        code: StatusCode::Cancelled as i32,
+        message: "Server is shutting down".to_string(),
+    }
+}
+
+async fn forward_shared_stream<M, F>(
+    session: &mut Session,
+    id: String,
+    mut subscription: Subscription,
+    handle: TaskHandle,
+    cmd_id: u16,
+    make_rsp: F,
+)
+where
+    M: Message + Name + Serialize + 'static,
+    F: Fn(String, i32, Option<Status>) -> M + Send + 'static,
+{
+    let task = handle.running.clone();
+    session.add_task(&id, handle).await;
+    let mut session = session.clone();
+    let shutdown = session.get_context().shutdown.clone();
+    tokio::spawn(async move {
+        while task.load(Ordering::Relaxed) {
+            let received = tokio::select! {
+                received = subscription.receiver.recv() => received,
+                _ = shutdown.cancelled() => {
+                    let rsp = make_rsp(id.clone(), 0, Some(cancelled_status()));
+                    let _ = session.send(cmd_id, rsp).await;
+                    break;
+                }
+            };
+            let number = match received {
+                Ok(number) => number,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            if !task.load(Ordering::Relaxed) {
+                break;
+            }
+            if session.send(cmd_id, make_rsp(id.clone(), number, None)).await.is_err() {
+                break;
+            }
+        }
+        session.get_context().shared_streams.unsubscribe(subscription).await;
+        session.remove_task(&id).await;
+    }.in_current_span());
+}
+
 pub async fn on_echo_request(
     session: &mut Session,
     msg: &EchoRequest
@@ -6,8 +6,56 @@ use tracing::Instrument;
 use proto::*;
 use super::session::Session;
 use super::packet::Packet;
+use super::protocol::JsonEnvelope;
+use super::rate_limit::{Verdict, UNKNOWN_CMD_ID};
 use handler_func::*;
 
+/// Charges an inbound frame to the rate limits of its command, replying when
+/// it is rejected and closing the session of persistent offenders. Frames
+/// naming no known command are charged to [`UNKNOWN_CMD_ID`].
+async fn charge(session: &mut Session, cmd_id: u16) -> Result<bool> {
+    match session.check_rate_limit(cmd_id).await {
+        Verdict::Allowed => Ok(true),
+        Verdict::Limited => {
+            send_command_status(session, cmd_id, StatusCode::RateLimited, "rate limited").await?;
+            Ok(false)
+        }
+        Verdict::Disconnect => {
+            tracing::warn!("Disconnecting session {} for exceeding rate limits", session.get_id());
+            let sessions = session.get_context().sessions.clone();
+            sessions.kick(session.get_id(), "Rate limit exceeded").await;
+            Ok(false)
+        }
+    }
+}
+
+/// Applies the authorization policy to an inbound command, replying when it
+/// is forbidden.
+async fn authorize(session: &mut Session, cmd_id: u16) -> Result<bool> {
+    if !session.get_context().policy.permits(session.get_identity(), cmd_id) {
+        tracing::warn!("{} is not allowed to invoke command {cmd_id}", session.get_identity().name);
+        send_command_status(session, cmd_id, StatusCode::Failure, "forbidden").await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+async fn send_command_status(
+    session: &mut Session,
+    cmd_id: u16,
+    code: StatusCode,
+    message: &str,
+) -> Result<()> {
+    let rsp = CommandStatusNotify {
+        cmd_id: cmd_id as u32,
+        status: Some(Status {
+            code: code as i32,
+            message: message.to_string(),
+        }),
+    };
+    session.send(cmd_id::COMMAND_STATUS_NOTIFY, rsp).await
+}
+
 macro_rules! trait_handler {
     ($($name:ident, $cmd_id:ident;)*) => {
         pub trait SessionCommandHandler {
@@ -22,12 +70,27 @@ macro_rules! trait_handler {
             async fn on_message(session: &mut Session, packet: Packet) -> Result<()> {
                 use ::prost::Message;
 
-                let cmd_id = packet.cmd_id;
+                let cmd_id = match packet.cmd_id {
+                    $(cmd_id::$cmd_id)|* => packet.cmd_id,
+                    _ => UNKNOWN_CMD_ID,
+                };
                 let msg = packet.msg;
+                let metrics = session.get_context().metrics.clone();
+                metrics.packet("in", cmd_id);
+                if !charge(session, cmd_id).await? {
+                    return Ok(());
+                }
                 match cmd_id {
                     $(
                         cmd_id:: $cmd_id => {
-                            let msg = $name::decode(&mut &msg[..])?;
+                            if !authorize(session, cmd_id).await? {
+                                return Ok(());
+                            }
+                            let msg = $name::decode(&mut &msg[..])
+                                .inspect_err(|_| metrics.decode_errors.inc())?;
+                            let _timer = metrics.handler_duration
+                                .with_label_values(&[&cmd_id.to_string()])
+                                .start_timer();
                             paste! {
                                 Self::[<on_$name:snake>](session, &msg)
                                     .instrument(tracing::info_span!(stringify!([<on_$name:snake>]), cmd_id = cmd_id))
@@ -36,11 +99,59 @@ macro_rules! trait_handler {
                         }
                     )*
                     _ => {
-                        tracing::warn!("Unknown command id: {cmd_id}");
+                        tracing::warn!("Unknown command id: {}", packet.cmd_id);
+                        Ok(())
+                    }
+                }
+            }
+
+            async fn on_json_message(session: &mut Session, envelope: JsonEnvelope) -> Result<()> {
+                let seq = envelope.seq;
+                let cmd_id = match envelope.cmd.as_str() {
+                    $(stringify!($name) => cmd_id::$cmd_id,)*
+                    _ => UNKNOWN_CMD_ID,
+                };
+                let metrics = session.get_context().metrics.clone();
+                metrics.packet("in", cmd_id);
+                if !charge(session, cmd_id).await? {
+                    return Ok(());
+                }
+                match envelope.cmd.as_str() {
+                    $(
+                        stringify!($name) => {
+                            if !authorize(session, cmd_id).await? {
+                                return Ok(());
+                            }
+                            let msg: $name = serde_json::from_value(envelope.body)
+                                .inspect_err(|_| metrics.decode_errors.inc())?;
+                            let _timer = metrics.handler_duration
+                                .with_label_values(&[&cmd_id::$cmd_id.to_string()])
+                                .start_timer();
+                            paste! {
+                                Self::[<on_$name:snake>](session, &msg)
+                                    .instrument(tracing::info_span!(stringify!([<on_$name:snake>]), cmd_id = cmd_id::$cmd_id, seq = seq))
+                                    .await
+                            }
+                        }
+                    )*
+                    cmd => {
+                        tracing::warn!("Unknown command: {cmd}");
                         Ok(())
                     }
                 }
             }
+
+            /// Frames which can not be decoded at all are charged to the rate
+            /// limits as well.
+            async fn on_invalid_message(session: &mut Session, err: anyhow::Error) -> Result<()> {
+                let metrics = session.get_context().metrics.clone();
+                metrics.packet("in", UNKNOWN_CMD_ID);
+                metrics.decode_errors.inc();
+                if charge(session, UNKNOWN_CMD_ID).await? {
+                    tracing::warn!("Invalid message: {err}");
+                }
+                Ok(())
+            }
         }
     };
 }
@@ -1,4 +1,8 @@
 mod packet;
-mod session;
+pub mod protocol;
+pub mod rate_limit;
+pub mod session;
+pub mod session_manager;
 mod handler;
+pub mod shared_stream;
 pub mod gateway;
@@ -1,4 +1,5 @@
 use std::io::{Cursor, Read};
+use anyhow::{ensure, Result};
 use byteorder::{ReadBytesExt, LittleEndian};
 
 const HEAD_MAGIC: u32 = 0x46415445; // FATE
@@ -6,36 +7,48 @@ const TAIL_MAGIC: u32 = 0x4C4F4F4D; // LOOM
 
 pub struct Packet {
     pub cmd_id: u16,
+    /// Per-session sequence number of the messages sent by the server, clients
+    /// may number their requests freely. Only the `fate-loom.v2` layout has
+    /// one, `None` lays the packet out without it.
+    pub seq: Option<u64>,
     size: u32,
     pub msg: Vec<u8>,
 }
 
-impl From<Vec<u8>> for Packet {
-    fn from(value: Vec<u8>) -> Self {
+impl Packet {
+    /// Parses a packet laid out with a sequence number if `sequenced`.
+    pub fn decode(value: Vec<u8>, sequenced: bool) -> Result<Self> {
         let mut cursor = Cursor::new(value);
 
-        let head_magic = cursor.read_u32::<LittleEndian>()
-            .expect("Fail to read head_magic");
-        assert_eq!(head_magic, HEAD_MAGIC);
+        let head_magic = cursor.read_u32::<LittleEndian>()?;
+        ensure!(head_magic == HEAD_MAGIC, "Invalid head magic {head_magic:#x}");
+
+        let cmd_id = cursor.read_u16::<LittleEndian>()?;
 
-        let cmd_id = cursor.read_u16::<LittleEndian>()
-            .expect("Fail to read cmd_id");
+        let seq = if sequenced {
+            Some(cursor.read_u64::<LittleEndian>()?)
+        } else {
+            None
+        };
 
-        let size = cursor.read_u32::<LittleEndian>()
-            .expect("Fail to read size");
+        let size = cursor.read_u32::<LittleEndian>()?;
+        ensure!(
+            size as u64 <= cursor.get_ref().len() as u64 - cursor.position(),
+            "Message size {size} exceeds the packet",
+        );
 
         let mut msg = vec![0; size as usize];
-        cursor.read_exact(&mut msg).expect("Fail to read msg");
+        cursor.read_exact(&mut msg)?;
 
-        let tail_magic = cursor.read_u32::<LittleEndian>()
-            .expect("Fail to read tail_magic");
-        assert_eq!(tail_magic, TAIL_MAGIC);
+        let tail_magic = cursor.read_u32::<LittleEndian>()?;
+        ensure!(tail_magic == TAIL_MAGIC, "Invalid tail magic {tail_magic:#x}");
 
-        Self {
+        Ok(Self {
             cmd_id,
+            seq,
             size,
             msg,
-        }
+        })
     }
 }
 
@@ -45,6 +58,9 @@ impl From<Packet> for Vec<u8> {
 
         out.extend(HEAD_MAGIC.to_le_bytes());
         out.extend(value.cmd_id.to_le_bytes());
+        if let Some(seq) = value.seq {
+            out.extend(seq.to_le_bytes());
+        }
         out.extend(value.size.to_le_bytes());
         out.extend(value.msg);
         out.extend(TAIL_MAGIC.to_le_bytes());
@@ -54,11 +70,52 @@ impl From<Packet> for Vec<u8> {
 }
 
 impl Packet {
-    pub fn new(cmd_id: u16, msg: Vec<u8>) -> Self {
+    pub fn new(cmd_id: u16, seq: Option<u64>, msg: Vec<u8>) -> Self {
         Self {
             cmd_id,
+            seq,
             size: msg.len() as u32,
             msg,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_both_layouts() {
+        for seq in [None, Some(7)] {
+            let bytes = Vec::<u8>::from(Packet::new(3, seq, b"hi".to_vec()));
+            assert_eq!(bytes.len(), if seq.is_some() { 24 } else { 16 });
+            let packet = Packet::decode(bytes, seq.is_some()).unwrap();
+            assert_eq!((packet.cmd_id, packet.seq, packet.msg), (3, seq, b"hi".to_vec()));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_packets() {
+        let bytes = Vec::<u8>::from(Packet::new(3, Some(7), b"hi".to_vec()));
+        for len in 0..bytes.len() {
+            assert!(Packet::decode(bytes[..len].to_vec(), true).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_messages() {
+        let mut bytes = Vec::<u8>::from(Packet::new(3, None, b"hi".to_vec()));
+        bytes[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Packet::decode(bytes, false).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = Vec::<u8>::from(Packet::new(3, None, b"hi".to_vec()));
+        let last = bytes.len() - 1;
+        bytes[last] = 0;
+        assert!(Packet::decode(bytes.clone(), false).is_err());
+        bytes[0] = 0;
+        assert!(Packet::decode(bytes, false).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// Wire format used by a session for the messages it sends.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    /// Protobuf messages framed in FATE/LOOM binary packets, without sequence
+    /// numbers for `fate-loom.v1` clients and those offering no subprotocol.
+    Binary,
+    /// Binary packets carrying the sequence number of the message.
+    SequencedBinary,
+    /// JSON envelopes carried in text frames.
+    Json,
+}
+
+/// Subprotocols accepted in `Sec-WebSocket-Protocol`, in order of preference.
+pub const SUBPROTOCOLS: &[(&str, Protocol)] = &[
+    ("fate-loom.v2", Protocol::SequencedBinary),
+    ("fate-loom.v1", Protocol::Binary),
+    ("fate-loom.json.v1", Protocol::Json),
+];
+
+impl Protocol {
+    pub fn is_binary(self) -> bool {
+        matches!(self, Protocol::Binary | Protocol::SequencedBinary)
+    }
+
+    /// Picks the preferred subprotocol among the comma separated ones offered
+    /// by the client.
+    pub fn negotiate(offered: &str) -> Option<(&'static str, Protocol)> {
+        SUBPROTOCOLS.iter()
+            .find(|(name, _)| offered.split(',').any(|offered| offered.trim() == *name))
+            .copied()
+    }
+}
+
+/// Text frame counterpart of [`Packet`](super::packet::Packet), e.g.
+/// `{"cmd":"EchoRequest","seq":1,"body":{"message":"hi"}}`.
+///
+/// `cmd` is the protobuf message name and `body` its serde mapping. Messages
+/// sent because of a request, including those of the streams it started,
+/// carry its `seq` in `reply_to`.
+#[derive(Deserialize, Serialize)]
+pub struct JsonEnvelope {
+    pub cmd: String,
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<u64>,
+    #[serde(default = "empty_body")]
+    pub body: serde_json::Value,
+}
+
+fn empty_body() -> serde_json::Value {
+    serde_json::Value::Object(serde_json::Map::new())
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use crate::config::server_config::{RateLimit, RateLimitConfig};
+
+/// Command id charged for the frames which name no known command.
+pub const UNKNOWN_CMD_ID: u16 = 0;
+
+const IP_BUCKETS_PRUNE_THRESHOLD: usize = 1024;
+const IP_BUCKETS_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    fn try_acquire(&mut self, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate).min(limit.burst as f64);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub enum Verdict {
+    Allowed,
+    Limited,
+    Disconnect,
+}
+
+/// Buckets of a single session, one for every command id it has sent.
+#[derive(Default)]
+pub struct SessionRateLimit {
+    buckets: HashMap<u16, TokenBucket>,
+    violations: u32,
+}
+
+#[derive(Default)]
+pub struct RateLimitStats {
+    pub session_limited: AtomicU64,
+    pub ip_limited: AtomicU64,
+    pub disconnected: AtomicU64,
+}
+
+/// Token bucket rate limiting of inbound messages, per session and command id
+/// and per remote IP across all of its sessions.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    ip_buckets: Arc<Mutex<HashMap<IpAddr, TokenBucket>>>,
+    pub stats: Arc<RateLimitStats>,
+}
+
+impl RateLimiter {
+    pub async fn check(&self, config: &RateLimitConfig, session: &mut SessionRateLimit, ip: IpAddr, cmd_id: u16) -> Verdict {
+        if !config.enabled {
+            return Verdict::Allowed;
+        }
+
+        let limit = config.commands.iter()
+            .find(|limit| limit.cmd_id == cmd_id)
+            .map_or(config.session, |limit| RateLimit {
+                rate: limit.rate,
+                burst: limit.burst,
+            });
+        let allowed = session.buckets
+            .entry(cmd_id)
+            .or_insert_with(|| TokenBucket::new(&limit))
+            .try_acquire(&limit);
+        let allowed = if allowed {
+            self.check_ip(&config.ip, ip).await
+        } else {
+            self.stats.session_limited.fetch_add(1, Ordering::Relaxed);
+            false
+        };
+
+        if allowed {
+            session.violations = 0;
+            return Verdict::Allowed;
+        }
+        session.violations += 1;
+        if config.disconnect_after > 0 && session.violations >= config.disconnect_after {
+            self.stats.disconnected.fetch_add(1, Ordering::Relaxed);
+            Verdict::Disconnect
+        } else {
+            Verdict::Limited
+        }
+    }
+
+    async fn check_ip(&self, limit: &RateLimit, ip: IpAddr) -> bool {
+        let mut buckets = self.ip_buckets.lock().await;
+        if buckets.len() > IP_BUCKETS_PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| bucket.updated.elapsed() < IP_BUCKETS_IDLE_TIMEOUT);
+        }
+        let allowed = buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(limit))
+            .try_acquire(limit);
+        if !allowed {
+            self.stats.ip_limited.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(disconnect_after: u32) -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            disconnect_after,
+            session: RateLimit { rate: 1.0, burst: 2 },
+            ip: RateLimit { rate: 100.0, burst: 100 },
+            commands: Vec::new(),
+        }
+    }
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn bucket_refills_up_to_burst() {
+        let limit = RateLimit { rate: 2.0, burst: 3 };
+        let mut bucket = TokenBucket::new(&limit);
+        for _ in 0..3 {
+            assert!(bucket.try_acquire(&limit));
+        }
+        assert!(!bucket.try_acquire(&limit));
+
+        bucket.updated -= Duration::from_secs(1);
+        assert!(bucket.try_acquire(&limit));
+        assert!(bucket.try_acquire(&limit));
+        assert!(!bucket.try_acquire(&limit));
+
+        bucket.updated -= Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(bucket.try_acquire(&limit));
+        }
+        assert!(!bucket.try_acquire(&limit));
+    }
+
+    #[tokio::test]
+    async fn disconnects_after_consecutive_violations() {
+        let limiter = RateLimiter::default();
+        let config = config(2);
+        let mut session = SessionRateLimit::default();
+        for _ in 0..2 {
+            assert!(matches!(limiter.check(&config, &mut session, IP, 1).await, Verdict::Allowed));
+        }
+        assert!(matches!(limiter.check(&config, &mut session, IP, 1).await, Verdict::Limited));
+        assert!(matches!(limiter.check(&config, &mut session, IP, 1).await, Verdict::Disconnect));
+        assert_eq!(limiter.stats.disconnected.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn allowed_message_resets_violations() {
+        let limiter = RateLimiter::default();
+        let config = config(2);
+        let mut session = SessionRateLimit::default();
+        for _ in 0..2 {
+            limiter.check(&config, &mut session, IP, 1).await;
+        }
+        assert!(matches!(limiter.check(&config, &mut session, IP, 1).await, Verdict::Limited));
+        // every command id has its own bucket
+        assert!(matches!(limiter.check(&config, &mut session, IP, 2).await, Verdict::Allowed));
+        assert!(matches!(limiter.check(&config, &mut session, IP, 1).await, Verdict::Limited));
+    }
+
+    #[tokio::test]
+    async fn never_disconnects_when_disabled() {
+        let limiter = RateLimiter::default();
+        let config = config(0);
+        let mut session = SessionRateLimit::default();
+        for _ in 0..10 {
+            assert!(!matches!(limiter.check(&config, &mut session, IP, 1).await, Verdict::Disconnect));
+        }
+    }
+}
@@ -1,57 +1,197 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tokio::sync::Mutex;
-use axum::extract::ws::{Message, WebSocket};
-use prost::Message as protoMessage;
-use anyhow::Result;
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use prost::{Message as protoMessage, Name};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
 use tokio::time::sleep;
 use std::time::Duration;
 use tokio::select;
+use crate::auth::Identity;
+use crate::net::gateway::Handshake;
 use crate::net::packet::Packet;
+use crate::net::protocol::{JsonEnvelope, Protocol};
+use crate::net::rate_limit::{SessionRateLimit, Verdict};
+use crate::net::shared_stream::StreamKind;
 use crate::net::handler::SessionCommandHandler;
+use crate::ServerContext;
+
+const RESUME_TOKEN_LEN: usize = 32;
+
+/// Outbound messages of a session, numbered by a per-session sequence number
+/// carried by every frame.
+///
+/// The last messages sent are kept so that a resumed session can replay the
+/// ones the client did not receive, in order.
+#[derive(Default)]
+struct Outbox {
+    last_seq: u64,
+    /// Sequence number of the last message written to a connection.
+    delivered_seq: u64,
+    sent: VecDeque<(u64, Message)>,
+}
+
+impl Outbox {
+    fn next_seq(&mut self) -> u64 {
+        self.last_seq += 1;
+        self.last_seq
+    }
+
+    fn buffer(&mut self, seq: u64, msg: Message, capacity: usize) {
+        while !self.sent.is_empty() && self.sent.len() >= capacity {
+            self.sent.pop_front();
+        }
+        if capacity > 0 {
+            self.sent.push_back((seq, msg));
+        }
+    }
+
+    /// The buffered messages following `seq`.
+    fn after(&self, seq: u64) -> Vec<(u64, Message)> {
+        self.sent.iter()
+            .filter(|(buffered, _)| *buffered > seq)
+            .cloned()
+            .collect()
+    }
+}
+
+/// A streaming task started by a request of the client.
+#[derive(Clone)]
+pub struct TaskHandle {
+    pub running: Arc<AtomicBool>,
+    pub kind: StreamKind,
+    /// The request which started the task.
+    pub params: serde_json::Value,
+    pub started_at: DateTime<Utc>,
+}
+
+impl TaskHandle {
+    pub fn new<M: Serialize>(running: Arc<AtomicBool>, kind: StreamKind, request: &M) -> Self {
+        Self {
+            running,
+            kind,
+            params: serde_json::to_value(request).unwrap_or_default(),
+            started_at: Utc::now(),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Session {
-    socket: Arc<Mutex<WebSocket>>,
-    tasks: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    id: u64,
+    peer: SocketAddr,
+    identity: Arc<Identity>,
+    resume_token: Arc<str>,
+    socket: Arc<Mutex<Option<WebSocket>>>,
+    outbox: Arc<Mutex<Outbox>>,
+    attach_epoch: Arc<AtomicU64>,
+    protocol: Arc<Mutex<Protocol>>,
+    subprotocol: Arc<Mutex<Option<Protocol>>>,
+    rate_limit: Arc<Mutex<SessionRateLimit>>,
+    tasks: Arc<Mutex<HashMap<String, TaskHandle>>>,
+    /// `seq` of the JSON request being handled, inherited by the tasks it starts.
+    reply_to: Option<u64>,
+    created_at: DateTime<Utc>,
+    context: ServerContext,
 }
 
 impl Session {
-    pub fn new(socket: WebSocket) -> Self {
+    /// Creates a session speaking the negotiated subprotocol, or following the
+    /// framing of the last request received if none was negotiated.
+    pub fn new(id: u64, socket: WebSocket, handshake: Handshake, context: ServerContext) -> Self {
+        let subprotocol = handshake.subprotocol;
+        let resume_token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(RESUME_TOKEN_LEN)
+            .map(char::from)
+            .collect();
         Self {
-            socket: Arc::new(Mutex::new(socket)),
+            id,
+            peer: handshake.peer,
+            identity: Arc::new(handshake.identity),
+            resume_token: resume_token.into(),
+            socket: Arc::new(Mutex::new(Some(socket))),
+            outbox: Arc::new(Mutex::new(Outbox::default())),
+            attach_epoch: Arc::new(AtomicU64::new(0)),
+            protocol: Arc::new(Mutex::new(subprotocol.unwrap_or(Protocol::Binary))),
+            subprotocol: Arc::new(Mutex::new(subprotocol)),
+            rate_limit: Arc::new(Mutex::new(SessionRateLimit::default())),
             tasks: Arc::new(Mutex::new(HashMap::new())),
+            reply_to: None,
+            created_at: Utc::now(),
+            context,
         }
     }
 
-    pub async fn run(&mut self) {
+    /// Handles the messages of the connection attached at `epoch`, until it
+    /// is closed or another connection resumes the session.
+    pub async fn run(&mut self, epoch: u64) {
         loop {
             let msg = loop {
                 let msg_opt = {
                     let mut socket = self.socket.lock().await;
+                    if self.attach_epoch() != epoch {
+                        return;
+                    }
+                    let Some(socket) = socket.as_mut() else {
+                        return;
+                    };
                     select! {
-                        msg = socket.recv() => msg,
+                        msg = socket.recv() => Some(msg),
                         _ = sleep(Duration::from_millis(100)) => None,
                     }
                 };
-                if let Some(msg) = msg_opt {
-                    break msg;
+                match msg_opt {
+                    Some(Some(msg)) => break msg,
+                    // client disconnected
+                    Some(None) => return,
+                    None => continue,
                 }
             };
 
             match msg {
                 Ok(Message::Text(text_msg)) => {
-                    let msg = Message::Text(text_msg);
-                    if self.socket.lock().await.send(msg).await.is_err() {
-                        // client disconnected
-                        return;
+                    self.context.metrics.bytes.with_label_values(&["in"]).inc_by(text_msg.len() as u64);
+                    if !self.switch_protocol(Protocol::Json).await {
+                        let err = anyhow!("Text frame on a binary subprotocol session");
+                        let _ = Self::on_invalid_message(self, err).await;
+                        continue;
+                    }
+                    let envelope = match serde_json::from_str::<JsonEnvelope>(&text_msg) {
+                        Ok(envelope) => envelope,
+                        Err(err) => {
+                            let err = anyhow!(err).context("Invalid JSON envelope");
+                            let _ = Self::on_invalid_message(self, err).await;
+                            continue;
+                        }
+                    };
+                    self.reply_to = Some(envelope.seq);
+                    let result = Self::on_json_message(self, envelope).await;
+                    self.reply_to = None;
+                    if let Err(err) = result {
+                        tracing::warn!("Error in handling JSON message received: {err}");
                     }
                 }
                 Ok(Message::Binary(bin_msg)) => {
-                    let packet = Packet::from(bin_msg);
-                    Self::on_message(self, packet).await
-                        .expect("Error in handling packet received");
+                    self.context.metrics.bytes.with_label_values(&["in"]).inc_by(bin_msg.len() as u64);
+                    if !self.switch_protocol(Protocol::Binary).await {
+                        let err = anyhow!("Binary frame on a JSON subprotocol session");
+                        let _ = Self::on_invalid_message(self, err).await;
+                        continue;
+                    }
+                    let sequenced = *self.protocol.lock().await == Protocol::SequencedBinary;
+                    let result = match Packet::decode(bin_msg, sequenced) {
+                        Ok(packet) => Self::on_message(self, packet).await,
+                        Err(err) => Self::on_invalid_message(self, err.context("Invalid packet")).await,
+                    };
+                    if let Err(err) = result {
+                        tracing::warn!("Error in handling packet received: {err}");
+                    }
                 }
                 Ok(Message::Ping(_ping_msg)) => {
                     continue;
@@ -59,8 +199,9 @@ impl Session {
                 Ok(Message::Pong(_pong_msg)) => {
                     continue;
                 }
-                Ok(_) => {
-                    continue;
+                Ok(Message::Close(_close_msg)) => {
+                    // client disconnected
+                    return;
                 }
                 Err(_) => {
                     // client disconnected
@@ -70,20 +211,173 @@ impl Session {
         }
     }
 
-    pub async fn send(&mut self, cmd_id: u16, msg: impl protoMessage) -> Result<()> {
-        let packet = Packet::new(cmd_id, msg.encode_to_vec());
-        let msg = Message::Binary(Vec::<u8>::from(packet));
+    /// Switches replies to the framing of a received frame, unless a
+    /// different subprotocol was negotiated for the connection.
+    async fn switch_protocol(&self, protocol: Protocol) -> bool {
+        match *self.subprotocol.lock().await {
+            Some(subprotocol) => subprotocol.is_binary() == protocol.is_binary(),
+            None => {
+                *self.protocol.lock().await = protocol;
+                true
+            }
+        }
+    }
+
+    pub async fn send<M>(&mut self, cmd_id: u16, msg: M) -> Result<()>
+    where
+        M: protoMessage + Name + Serialize,
+    {
+        let protocol = *self.protocol.lock().await;
         let mut socket = self.socket.lock().await;
-        socket.send(msg).await?;
+        let mut outbox = self.outbox.lock().await;
+        let seq = outbox.next_seq();
+        let msg = match protocol {
+            Protocol::Binary | Protocol::SequencedBinary => {
+                let seq = (protocol == Protocol::SequencedBinary).then_some(seq);
+                let packet = Packet::new(cmd_id, seq, msg.encode_to_vec());
+                Message::Binary(Vec::<u8>::from(packet))
+            }
+            Protocol::Json => {
+                let envelope = JsonEnvelope {
+                    cmd: M::NAME.to_string(),
+                    seq,
+                    reply_to: self.reply_to,
+                    body: serde_json::to_value(&msg)?,
+                };
+                Message::Text(serde_json::to_string(&envelope)?)
+            }
+        };
+        let metrics = &self.context.metrics;
+        metrics.packet("out", cmd_id);
+        let len = match &msg {
+            Message::Binary(bin_msg) => bin_msg.len(),
+            Message::Text(text_msg) => text_msg.len(),
+            _ => 0,
+        };
+        metrics.bytes.with_label_values(&["out"]).inc_by(len as u64);
+        // a write may succeed on a connection which is already gone, so the
+        // message is kept until it falls out of the buffer anyway
+        let capacity = self.context.config.load().websocket.resume_buffer_size;
+        outbox.buffer(seq, msg.clone(), capacity);
+        if let Some(ws) = socket.as_mut() {
+            if ws.send(msg).await.is_ok() {
+                outbox.delivered_seq = seq;
+            } else {
+                // client disconnected, the message is replayed once the session is resumed
+                *socket = None;
+            }
+        }
         Ok(())
     }
 
+    /// Attaches a new connection to the session and replays the messages
+    /// following `last_seq`, the last sequence number received by the client,
+    /// or those never written to a connection if it is not known. `fate-loom.v1`
+    /// clients never see sequence numbers, so theirs is ignored. A connection
+    /// still attached is closed, since a dropped one may not have been noticed
+    /// yet. Returns the new attach epoch.
+    pub async fn attach(&mut self, mut ws: WebSocket, subprotocol: Option<Protocol>, last_seq: Option<u64>) -> Result<u64> {
+        let mut socket = self.socket.lock().await;
+        let mut outbox = self.outbox.lock().await;
+        let last_seq = last_seq.filter(|_| subprotocol != Some(Protocol::Binary));
+        let after = last_seq.unwrap_or(outbox.delivered_seq);
+        let replay = outbox.after(after);
+        if replay.first().map_or(after < outbox.last_seq, |(seq, _)| *seq > after + 1) {
+            tracing::warn!("Session {} dropped messages after {after} which no longer fit in the resume buffer", self.id);
+        }
+        for (seq, msg) in replay {
+            ws.send(msg).await?;
+            outbox.delivered_seq = seq;
+        }
+        if let Some(protocol) = subprotocol {
+            *self.protocol.lock().await = protocol;
+        }
+        *self.subprotocol.lock().await = subprotocol;
+
+        if let Some(mut previous) = socket.replace(ws) {
+            tracing::info!("Session {} resumed by another connection, closing the previous one", self.id);
+            tokio::spawn(async move {
+                let frame = CloseFrame {
+                    code: 1008,
+                    reason: "Session resumed by another connection".into(),
+                };
+                let _ = previous.send(Message::Close(Some(frame))).await;
+            });
+        }
+        Ok(self.attach_epoch.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    /// Drops the connection attached at `epoch`, unless another connection has
+    /// resumed the session meanwhile. Returns whether the session was detached.
+    pub async fn detach(&mut self, epoch: u64) -> bool {
+        let mut socket = self.socket.lock().await;
+        if self.attach_epoch() != epoch {
+            return false;
+        }
+        *socket = None;
+        true
+    }
+
+    pub async fn is_attached(&self) -> bool {
+        self.socket.lock().await.is_some()
+    }
+
+    pub fn attach_epoch(&self) -> u64 {
+        self.attach_epoch.load(Ordering::Relaxed)
+    }
+
+    pub async fn last_seq(&self) -> u64 {
+        self.outbox.lock().await.last_seq
+    }
+
+    pub async fn check_rate_limit(&self, cmd_id: u16) -> Verdict {
+        let config = self.context.config.load_full();
+        let mut rate_limit = self.rate_limit.lock().await;
+        self.context.rate_limiter.check(&config.rate_limit, &mut rate_limit, self.peer.ip(), cmd_id).await
+    }
+
+    /// Sends a close frame and drops the connection without waiting for the
+    /// client to answer it, which ends [`Session::run`].
+    pub async fn close(&mut self, code: u16, reason: &'static str) {
+        let mut socket = self.socket.lock().await;
+        if let Some(mut ws) = socket.take() {
+            let frame = CloseFrame {
+                code,
+                reason: reason.into(),
+            };
+            let _ = ws.send(Message::Close(Some(frame))).await;
+        }
+    }
+
+    pub async fn wait_tasks(&self) {
+        while !self.tasks.lock().await.is_empty() {
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    pub async fn stop_tasks(&mut self) {
+        let tasks = self.tasks.lock().await;
+        for task in tasks.values() {
+            task.running.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Stops the task `id`, returns `false` if there is no such task.
+    pub async fn stop_task(&self, id: &str) -> bool {
+        let tasks = self.tasks.lock().await;
+        let Some(task) = tasks.get(id) else {
+            return false;
+        };
+        task.running.store(false, Ordering::Relaxed);
+        true
+    }
+
     pub async fn include_task(&self, id: &String) -> bool {
         let tasks = self.tasks.lock().await;
         tasks.contains_key(id)
     }
 
-    pub async fn add_task(&mut self, id: &String, task: Arc<AtomicBool>) {
+    pub async fn add_task(&mut self, id: &String, task: TaskHandle) {
         let mut tasks = self.tasks.lock().await;
         tasks.insert(id.clone(), task);
     }
@@ -96,16 +390,40 @@ impl Session {
     pub async fn store_task(&mut self, id: &String, value: bool) {
         let tasks = self.tasks.lock().await;
         let task = tasks.get(id).unwrap();
-        task.store(value, Ordering::Relaxed);
+        task.running.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
     }
 
-    pub fn get_socket(&mut self) -> Arc<Mutex<WebSocket>> {
+    pub fn get_peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    pub fn get_identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    pub fn get_resume_token(&self) -> &str {
+        &self.resume_token
+    }
+
+    pub fn get_socket(&mut self) -> Arc<Mutex<Option<WebSocket>>> {
         self.socket.clone()
     }
 
-    pub fn get_tasks(&mut self) -> Arc<Mutex<HashMap<String, Arc<AtomicBool>>>> {
+    pub fn get_tasks(&self) -> Arc<Mutex<HashMap<String, TaskHandle>>> {
         self.tasks.clone()
     }
+
+    pub fn get_created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn get_context(&self) -> &ServerContext {
+        &self.context
+    }
 }
 
 impl SessionCommandHandler for Session {}
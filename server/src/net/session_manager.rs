@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use axum::extract::ws::WebSocket;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use proto::*;
+use crate::auth::{constant_time_eq, Identity};
+use crate::net::gateway::Handshake;
+use crate::net::session::Session;
+use crate::ServerContext;
+
+/// Registry of the sessions alive on this server, including the detached ones
+/// that can still be resumed with their resume token.
+#[derive(Clone, Default)]
+pub struct SessionManager {
+    next_id: Arc<AtomicU64>,
+    sessions: Arc<Mutex<HashMap<u64, Session>>>,
+}
+
+impl SessionManager {
+    pub async fn create(
+        &self,
+        socket: WebSocket,
+        handshake: Handshake,
+        context: ServerContext,
+    ) -> Session {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let session = Session::new(id, socket, handshake, context);
+        self.sessions.lock().await.insert(id, session.clone());
+        session
+    }
+
+    /// Finds the session owning `resume_token`, if it has not expired yet and
+    /// belongs to `identity`. The session may still be attached to a
+    /// connection which [`Session::attach`] then takes over.
+    pub async fn find_resumable(&self, resume_token: &str, identity: &Identity) -> Option<Session> {
+        let session = {
+            let sessions = self.sessions.lock().await;
+            sessions.values()
+                .find(|session| constant_time_eq(session.get_resume_token().as_bytes(), resume_token.as_bytes()))
+                .cloned()
+        }?;
+        if session.get_identity() != identity {
+            tracing::warn!("Refusing to resume session {} as {}", session.get_id(), identity.name);
+            return None;
+        }
+        Some(session)
+    }
+
+    pub async fn list(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self.sessions.lock().await.values().cloned().collect();
+        sessions.sort_by_key(Session::get_id);
+        sessions
+    }
+
+    pub async fn find(&self, id: u64) -> Option<Session> {
+        self.sessions.lock().await.get(&id).cloned()
+    }
+
+    /// Stops the tasks of a session and closes it for good with `1008`, it can
+    /// not be resumed afterwards.
+    pub async fn kick(&self, id: u64, reason: &'static str) -> bool {
+        let Some(mut session) = self.sessions.lock().await.remove(&id) else {
+            return false;
+        };
+        tracing::info!("Kicking session {id}: {reason}");
+        session.stop_tasks().await;
+        session.close(1008, reason).await;
+        true
+    }
+
+    /// Detaches the connection attached to a session at `epoch` and expires the
+    /// session unless it is resumed within the configured grace period.
+    pub async fn detach(&self, session: &mut Session, epoch: u64) {
+        if !session.detach(epoch).await {
+            return;
+        }
+        let grace = session.get_context().config.load().websocket.resume_grace_secs;
+        let manager = self.clone();
+        let mut session = session.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(grace)).await;
+            if session.attach_epoch() == epoch {
+                tracing::debug!("Session {} expired", session.get_id());
+                session.stop_tasks().await;
+                manager.sessions.lock().await.remove(&session.get_id());
+            }
+        });
+    }
+
+    /// Notifies every session that the server is going away, waits for their
+    /// streaming tasks to end and closes the connections with `1001`.
+    ///
+    /// The notices go out before `shutdown` is cancelled, which stops the
+    /// streaming tasks and the rest of the server.
+    pub async fn shutdown(&self, shutdown: &CancellationToken) {
+        let sessions: Vec<Session> = {
+            let mut sessions = self.sessions.lock().await;
+            sessions.drain().map(|(_, session)| session).collect()
+        };
+
+        for session in sessions.iter() {
+            let notice = ServerShutdownNotice {
+                message: "Server is shutting down".to_string(),
+                reconnect_after_secs: session.get_context().config.load().shutdown.reconnect_after_secs,
+            };
+            let _ = session.clone().send(cmd_id::SERVER_SHUTDOWN_NOTICE, notice).await;
+        }
+        shutdown.cancel();
+
+        for mut session in sessions {
+            session.wait_tasks().await;
+            session.close(1001, "Server is shutting down").await;
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use anyhow::{bail, Result};
+use serde::Serialize;
+use tokio::sync::{broadcast, Mutex};
+
+const CHANNEL_CAPACITY: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamKind {
+    RandomNumber,
+    IncrementalSequence,
+}
+
+impl StreamKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamKind::RandomNumber => "random_number",
+            StreamKind::IncrementalSequence => "incremental_sequence",
+        }
+    }
+}
+
+/// Parameters of a generator, `min`/`max` of a random number stream or
+/// `start`/`end` of an incremental sequence. Every subscriber of a shared key
+/// must ask for the same ones.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StreamParams {
+    pub low: i32,
+    pub high: i32,
+    pub interval: u64,
+}
+
+struct SharedStream {
+    kind: StreamKind,
+    params: StreamParams,
+    sender: broadcast::Sender<i32>,
+    running: Arc<AtomicBool>,
+    subscribers: usize,
+}
+
+pub struct Subscription {
+    key: String,
+    running: Arc<AtomicBool>,
+    pub receiver: broadcast::Receiver<i32>,
+}
+
+/// Generators shared between sessions, keyed by the `shared_key` of the request.
+///
+/// The first subscriber starts the generator, later subscribers join it and
+/// the generator stops once the last subscriber leaves.
+#[derive(Clone, Default)]
+pub struct SharedStreams {
+    streams: Arc<Mutex<HashMap<String, SharedStream>>>,
+}
+
+impl SharedStreams {
+    pub async fn subscribe<F>(
+        &self,
+        key: &str,
+        kind: StreamKind,
+        params: StreamParams,
+        generator: F,
+    ) -> Result<Subscription>
+    where
+        F: FnMut() -> Option<i32> + Send + 'static,
+    {
+        let mut streams = self.streams.lock().await;
+        if let Some(stream) = streams.get_mut(key) {
+            if stream.kind != kind {
+                bail!("Shared stream {key} is already used by another kind of stream");
+            }
+            if stream.params != params {
+                bail!("Shared stream {key} is already running with different parameters");
+            }
+            stream.subscribers += 1;
+            return Ok(Subscription {
+                key: key.to_string(),
+                running: stream.running.clone(),
+                receiver: stream.sender.subscribe(),
+            });
+        }
+
+        let (sender, receiver) = broadcast::channel(CHANNEL_CAPACITY);
+        let running = Arc::new(AtomicBool::new(true));
+        streams.insert(key.to_string(), SharedStream {
+            kind,
+            params,
+            sender: sender.clone(),
+            running: running.clone(),
+            subscribers: 1,
+        });
+        tokio::spawn(Self::generate(
+            self.clone(), key.to_string(), sender, running.clone(), params.interval, generator,
+        ));
+        Ok(Subscription {
+            key: key.to_string(),
+            running,
+            receiver,
+        })
+    }
+
+    pub async fn unsubscribe(&self, subscription: Subscription) {
+        let key = subscription.key.as_str();
+        let mut streams = self.streams.lock().await;
+        if let Some(stream) = streams.get_mut(key) {
+            // the key may already belong to a newer stream if ours has finished
+            if !Arc::ptr_eq(&stream.running, &subscription.running) {
+                return;
+            }
+            stream.subscribers -= 1;
+            if stream.subscribers == 0 {
+                stream.running.store(false, Ordering::Relaxed);
+                streams.remove(key);
+            }
+        }
+    }
+
+    async fn generate<F>(
+        self,
+        key: String,
+        sender: broadcast::Sender<i32>,
+        running: Arc<AtomicBool>,
+        interval: u64,
+        mut generator: F,
+    )
+    where
+        F: FnMut() -> Option<i32> + Send + 'static,
+    {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval));
+        while running.load(Ordering::Relaxed) {
+            interval.tick().await;
+            if !running.load(Ordering::Relaxed) {
+                break;
+            }
+            match generator() {
+                // no receiver only means every subscriber is between two ticks
+                Some(number) => { let _ = sender.send(number); }
+                None => break,
+            }
+        }
+
+        // a finished generator must not stay joinable, but a newer stream may
+        // already have taken over the key after the last subscriber left
+        let mut streams = self.streams.lock().await;
+        if streams.get(&key).is_some_and(|stream| Arc::ptr_eq(&stream.running, &running)) {
+            streams.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use anyhow::Result;
+use serde_json::Value;
+use crate::ServerContext;
+use crate::config::{self, ConfigOverrides};
+use crate::config::server_config::ServerConfig;
+use crate::util::LogFilter;
+
+/// Keys applied to the running server, changing any other key requires a restart.
+const RELOADABLE_KEYS: &[&str] = &[
+    "log.filter",
+    "http.cache_max_age_secs",
+    "rate_limit",
+    "proxy.routes.*.forward_to",
+    "proxy.routes.*.rewrite",
+    "proxy.routes.*.timeout_ms",
+    "proxy.routes.*.max_body_size",
+    "proxy.routes.*.preserve_host",
+    "proxy.routes.*.x_forwarded",
+    "proxy.routes.*.forwarded",
+    "proxy.routes.*.request_headers",
+    "proxy.routes.*.response_headers",
+];
+
+/// Dotted keys which differ between two configurations.
+#[derive(Default)]
+pub struct ConfigChanges {
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+}
+
+/// Builds the configuration to run with from the `current` one and a newly
+/// resolved one: reloadable keys take their new value while the others are
+/// kept until the next restart.
+pub fn apply_reloadable(current: &ServerConfig, resolved: &ServerConfig) -> Result<(ServerConfig, ConfigChanges)> {
+    let mut value = serde_json::to_value(current)?;
+    let resolved = serde_json::to_value(resolved)?;
+    let mut changed = Vec::new();
+    diff(&value, &resolved, "", &mut changed);
+
+    let mut changes = ConfigChanges::default();
+    for key in changed {
+        if !RELOADABLE_KEYS.iter().any(|reloadable| is_under(&key, reloadable)) {
+            changes.restart_required.push(key);
+            continue;
+        }
+        let pointer = json_pointer(&key);
+        let new_value = resolved.pointer(&pointer).cloned().unwrap_or(Value::Null);
+        if let Some(slot) = value.pointer_mut(&pointer) {
+            *slot = new_value;
+        }
+        changes.applied.push(key);
+    }
+    Ok((serde_json::from_value(value)?, changes))
+}
+
+/// Whether the dotted `key` is `pattern` or below it, `*` matching any segment.
+fn is_under(key: &str, pattern: &str) -> bool {
+    let mut segments = key.split('.');
+    pattern.split('.').all(|expected| segments.next().is_some_and(|segment| expected == "*" || segment == expected))
+}
+
+fn json_pointer(key: &str) -> String {
+    key.split('.')
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Collects the dotted keys of the leaves which differ. Arrays of the same
+/// length are compared item by item, e.g. `proxy.routes.0.forward_to`.
+fn diff(old: &Value, new: &Value, path: &str, changed: &mut Vec<String>) {
+    let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let keys = old.keys().chain(new.keys().filter(|key| !old.contains_key(*key)));
+            for key in keys {
+                diff(old.get(key).unwrap_or(&Value::Null), new.get(key).unwrap_or(&Value::Null), &join(key), changed);
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+            for (index, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+                diff(old, new, &join(&index.to_string()), changed);
+            }
+        }
+        (old, new) if old != new => changed.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// Reloads the configuration when the configuration file changes or on
+/// SIGHUP, until the server shuts down.
+pub fn watch_config(context: ServerContext, overrides: ConfigOverrides, log_filter: LogFilter) {
+    let interval_secs = context.config.load().reload_interval_secs;
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup_signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("Failed to install SIGHUP handler");
+        let mut interval = (interval_secs > 0)
+            .then(|| tokio::time::interval(Duration::from_secs(interval_secs)));
+        let path = overrides.config_path().to_path_buf();
+        let mut last_modified = modified_at(&path);
+        loop {
+            let tick = async {
+                match interval.as_mut() {
+                    Some(interval) => { interval.tick().await; }
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(unix)]
+            let hangup = hangup_signal.recv();
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = tick => {
+                    let modified = modified_at(&path);
+                    if modified.is_none() || modified == last_modified {
+                        continue;
+                    }
+                    last_modified = modified;
+                    tracing::info!("{} changed, reloading the configuration", path.display());
+                }
+                _ = hangup => tracing::info!("Received SIGHUP, reloading the configuration"),
+                _ = context.shutdown.cancelled() => return,
+            }
+            reload(&context, &overrides, &log_filter);
+        }
+    });
+}
+
+fn reload(context: &ServerContext, overrides: &ConfigOverrides, log_filter: &LogFilter) {
+    // keep running with the current configuration when the new one is invalid
+    let resolved = match config::resolve_config(overrides) {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            tracing::error!("Configuration was not reloaded: {err:#}");
+            return;
+        }
+    };
+    for warning in resolved.warnings.iter() {
+        tracing::warn!("Configuration {warning}");
+    }
+
+    let (config, changes) = match apply_reloadable(&context.config.load(), &resolved.config) {
+        Ok(result) => result,
+        Err(err) => {
+            tracing::error!("Configuration was not reloaded: {err:#}");
+            return;
+        }
+    };
+    let filter = config.log.filter.clone();
+    context.config.store(Arc::new(config));
+
+    for key in changes.applied.iter() {
+        tracing::info!("Configuration {key} is applied");
+    }
+    for key in changes.restart_required.iter() {
+        tracing::warn!("Configuration {key} changed, restart the server to apply it");
+    }
+    if changes.applied.is_empty() && changes.restart_required.is_empty() {
+        tracing::info!("Configuration is unchanged");
+    }
+    // last, so that the messages above are not hidden by the new filter
+    if changes.applied.iter().any(|key| key == "log.filter") {
+        if let Err(err) = log_filter.set(&filter) {
+            tracing::error!("Failed to apply log.filter: {err:#}");
+        }
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
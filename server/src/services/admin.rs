@@ -0,0 +1,138 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::ServerContext;
+use crate::auth::constant_time_eq;
+use crate::config::server_config::ServerConfig;
+use crate::net::session::Session;
+use crate::net::shared_stream::StreamKind;
+
+pub fn setup_routes(router: Router<ServerContext>, config: &ServerConfig) -> Router<ServerContext> {
+    let config = &config.admin;
+    if !config.enabled {
+        return router;
+    }
+    if config.token.is_empty() {
+        tracing::warn!("The admin API is enabled without a token, it will not be served");
+        return router;
+    }
+    let admin = Router::new()
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", get(get_session).delete(kick_session))
+        .route("/sessions/:id/tasks/:task_id", delete(stop_task))
+        .route_layer(middleware::from_fn_with_state(config.token.clone(), require_admin_token));
+    router.nest(config.base_path.trim_end_matches('/'), admin)
+}
+
+async fn require_admin_token(State(admin_token): State<String>, req: Request, next: Next) -> Response {
+    let token = req.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .unwrap_or_default();
+    if !constant_time_eq(token.as_bytes(), admin_token.as_bytes()) {
+        tracing::warn!("Rejected admin request to {}", req.uri().path());
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(req).await
+}
+
+#[derive(Serialize)]
+struct TaskInfo {
+    id: String,
+    kind: StreamKind,
+    params: serde_json::Value,
+    started_at: DateTime<Utc>,
+    uptime_secs: i64,
+}
+
+#[derive(Serialize)]
+struct SessionInfo {
+    id: u64,
+    peer: String,
+    identity: String,
+    roles: Vec<String>,
+    attached: bool,
+    last_seq: u64,
+    created_at: DateTime<Utc>,
+    uptime_secs: i64,
+    tasks: Vec<TaskInfo>,
+}
+
+impl SessionInfo {
+    async fn from_session(session: &Session) -> Self {
+        let now = Utc::now();
+        let mut tasks: Vec<TaskInfo> = session.get_tasks().lock().await
+            .iter()
+            .map(|(id, task)| TaskInfo {
+                id: id.clone(),
+                kind: task.kind,
+                params: task.params.clone(),
+                started_at: task.started_at,
+                uptime_secs: (now - task.started_at).num_seconds(),
+            })
+            .collect();
+        tasks.sort_by(|a, b| a.id.cmp(&b.id));
+        let identity = session.get_identity();
+        Self {
+            id: session.get_id(),
+            peer: session.get_peer().to_string(),
+            identity: identity.name.clone(),
+            roles: identity.roles.clone(),
+            attached: session.is_attached().await,
+            last_seq: session.last_seq().await,
+            created_at: session.get_created_at(),
+            uptime_secs: (now - session.get_created_at()).num_seconds(),
+            tasks,
+        }
+    }
+}
+
+async fn list_sessions(State(context): State<ServerContext>) -> Json<Vec<SessionInfo>> {
+    let mut sessions = Vec::new();
+    for session in context.sessions.list().await {
+        sessions.push(SessionInfo::from_session(&session).await);
+    }
+    Json(sessions)
+}
+
+async fn get_session(
+    State(context): State<ServerContext>,
+    Path(id): Path<u64>,
+) -> Result<Json<SessionInfo>, StatusCode> {
+    let session = context.sessions.find(id).await.ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(SessionInfo::from_session(&session).await))
+}
+
+async fn kick_session(
+    State(context): State<ServerContext>,
+    Path(id): Path<u64>,
+) -> StatusCode {
+    if context.sessions.kick(id, "Kicked by an operator").await {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn stop_task(
+    State(context): State<ServerContext>,
+    Path((id, task_id)): Path<(u64, String)>,
+) -> StatusCode {
+    let Some(session) = context.sessions.find(id).await else {
+        return StatusCode::NOT_FOUND;
+    };
+    if session.stop_task(&task_id).await {
+        tracing::info!("Stopped task {task_id} of session {id}");
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
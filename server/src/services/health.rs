@@ -0,0 +1,106 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use axum::{
+    Json, Router,
+    routing::get,
+    extract::State,
+    http::{StatusCode, Uri},
+};
+use serde::Serialize;
+use tokio::net::TcpStream;
+use crate::ServerContext;
+use crate::config::server_config::ServerConfig;
+
+pub fn setup_routes(router: Router<ServerContext>, config: &ServerConfig) -> Router<ServerContext> {
+    router
+        .route(config.health.liveness_path.as_str(), get(liveness))
+        .route(config.health.readiness_path.as_str(), get(readiness))
+}
+
+#[derive(Serialize)]
+struct Check {
+    name: &'static str,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self { name, ok: true, message: None },
+            Err(message) => Self { name, ok: false, message: Some(message) },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    checks: Vec<Check>,
+}
+
+impl Health {
+    fn respond(checks: Vec<Check>) -> (StatusCode, Json<Health>) {
+        if checks.iter().all(|check| check.ok) {
+            (StatusCode::OK, Json(Health { status: "ok", checks }))
+        } else {
+            (StatusCode::SERVICE_UNAVAILABLE, Json(Health { status: "failing", checks }))
+        }
+    }
+}
+
+async fn liveness() -> (StatusCode, Json<Health>) {
+    Health::respond(Vec::new())
+}
+
+async fn readiness(State(context): State<ServerContext>) -> (StatusCode, Json<Health>) {
+    let config = context.config.load_full();
+    let mut checks = vec![
+        Check::new("shutdown", if context.shutdown.is_cancelled() {
+            Err("Server is shutting down".to_string())
+        } else {
+            Ok(())
+        }),
+        Check::new("listener", if context.listening.load(Ordering::Relaxed) {
+            Ok(())
+        } else {
+            Err("Listeners are not bound yet".to_string())
+        }),
+        // the configuration is loaded before anything is served
+        Check::new("config", Ok(())),
+        Check::new("dist_path", check_dist_path(&config.http.dist_path).await),
+    ];
+    if !config.proxy.routes.is_empty() {
+        let timeout = Duration::from_millis(config.health.upstream_timeout_ms);
+        let mut failures = Vec::new();
+        for route in config.proxy.routes.iter() {
+            if let Err(message) = check_upstream(&route.forward_to, timeout).await {
+                failures.push(format!("{}: {message}", route.prefix));
+            }
+        }
+        let result = if failures.is_empty() { Ok(()) } else { Err(failures.join("; ")) };
+        checks.push(Check::new("proxy_upstream", result));
+    }
+    Health::respond(checks)
+}
+
+async fn check_dist_path(dist_path: &str) -> Result<(), String> {
+    match tokio::fs::metadata(dist_path).await {
+        Ok(meta) if meta.is_dir() => Ok(()),
+        Ok(_) => Err(format!("{dist_path} is not a directory")),
+        Err(err) => Err(format!("{dist_path}: {err}")),
+    }
+}
+
+/// Checks that a TCP connection to the upstream can be opened.
+async fn check_upstream(forward_to: &str, timeout: Duration) -> Result<(), String> {
+    let uri = forward_to.parse::<Uri>().map_err(|err| format!("Invalid upstream {forward_to}: {err}"))?;
+    let host = uri.host().ok_or_else(|| format!("Upstream {forward_to} has no host"))?;
+    let port = uri.port_u16().unwrap_or(80);
+    match tokio::time::timeout(timeout, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) => Err(format!("{host}:{port}: {err}")),
+        Err(_) => Err(format!("{host}:{port}: timed out")),
+    }
+}
@@ -0,0 +1,27 @@
+use axum::{
+    Router,
+    routing::get,
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use crate::ServerContext;
+use crate::config::server_config::ServerConfig;
+
+pub fn setup_routes(router: Router<ServerContext>, config: &ServerConfig) -> Router<ServerContext> {
+    if !config.metrics.enabled {
+        return router;
+    }
+    router
+        .route(config.metrics.path.as_str(), get(metrics))
+}
+
+async fn metrics(State(context): State<ServerContext>) -> Response {
+    match context.metrics.encode(&context).await {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(err) => {
+            tracing::error!("Failed to encode metrics: {err:#}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -1,3 +1,6 @@
 pub mod web;
 pub mod websocket;
 pub mod reverse_proxy;
+pub mod admin;
+pub mod metrics;
+pub mod health;
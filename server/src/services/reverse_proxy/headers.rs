@@ -0,0 +1,226 @@
+use std::net::IpAddr;
+use axum::http::{header, header::IntoHeaderName, HeaderMap, HeaderName, HeaderValue};
+use crate::config::server_config::ProxyRoute;
+
+/// Headers which only apply to a single connection, RFC 7230 section 6.1.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+
+/// Where a proxied request comes from, as seen by the proxy.
+pub struct Origin {
+    pub peer: IpAddr,
+    pub scheme: &'static str,
+    pub host: Option<HeaderValue>,
+}
+
+/// Removes the hop-by-hop headers along with those the `Connection` header
+/// names.
+pub fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers.get_all(header::CONNECTION).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in listed {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP {
+        headers.remove(*name);
+    }
+}
+
+/// Prepares the headers of a request before it is forwarded: hop-by-hop
+/// headers are removed, `Host` is left to the upstream URI unless the route
+/// preserves it, and the forwarding headers describe the client. Forwarding
+/// headers sent by the client are only kept when `trust_forwarded` is set,
+/// since they can not be told apart from spoofed ones otherwise.
+pub fn prepare_request(headers: &mut HeaderMap, route: &ProxyRoute, trust_forwarded: bool, origin: &Origin) {
+    // `TE: trailers` is the only value which may be passed on
+    let te_trailers = headers.get_all(header::TE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case("trailers"));
+    remove_hop_by_hop(headers);
+    if te_trailers {
+        headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+
+    if !route.preserve_host {
+        headers.remove(header::HOST);
+    }
+
+    if !trust_forwarded {
+        for name in [X_FORWARDED_FOR, X_FORWARDED_PROTO, X_FORWARDED_HOST] {
+            headers.remove(name);
+        }
+        headers.remove(header::FORWARDED);
+    }
+
+    if route.x_forwarded {
+        let forwarded_for = match joined(headers, X_FORWARDED_FOR) {
+            Some(previous) => format!("{previous}, {}", origin.peer),
+            None => origin.peer.to_string(),
+        };
+        insert(headers, X_FORWARDED_FOR, &forwarded_for);
+        // an earlier proxy saw the original request
+        if !headers.contains_key(X_FORWARDED_PROTO) {
+            insert(headers, X_FORWARDED_PROTO, origin.scheme);
+        }
+        if let Some(host) = &origin.host {
+            if !headers.contains_key(X_FORWARDED_HOST) {
+                headers.insert(X_FORWARDED_HOST, host.clone());
+            }
+        }
+    }
+
+    if route.forwarded {
+        let element = forwarded_element(origin);
+        let forwarded = match joined(headers, header::FORWARDED.as_str()) {
+            Some(previous) => format!("{previous}, {element}"),
+            None => element,
+        };
+        insert(headers, header::FORWARDED, &forwarded);
+    }
+}
+
+/// A `Forwarded` element of RFC 7239, e.g. `for="[2001:db8::1]";proto=https;host="example.com"`.
+fn forwarded_element(origin: &Origin) -> String {
+    let peer = match origin.peer {
+        IpAddr::V4(peer) => peer.to_string(),
+        IpAddr::V6(peer) => format!("\"[{peer}]\""),
+    };
+    let mut element = format!("for={peer};proto={}", origin.scheme);
+    if let Some(host) = origin.host.as_ref().and_then(|host| host.to_str().ok()) {
+        element.push_str(&format!(";host=\"{}\"", host.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    element
+}
+
+/// The values of a header sent on several lines, as a single list.
+fn joined(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<&str> = headers.get_all(name).iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+fn insert(headers: &mut HeaderMap, name: impl IntoHeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::try_from(value) {
+        headers.insert(name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn route(x_forwarded: bool, forwarded: bool) -> ProxyRoute {
+        serde_json::from_value(json!({
+            "prefix": "/api",
+            "forward_to": "http://upstream:9000",
+            "x_forwarded": x_forwarded,
+            "forwarded": forwarded,
+        })).unwrap()
+    }
+
+    fn origin() -> Origin {
+        Origin {
+            peer: "203.0.113.7".parse().unwrap(),
+            scheme: "https",
+            host: Some(HeaderValue::from_static("example.com")),
+        }
+    }
+
+    fn header_map(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn strips_hop_by_hop_headers() {
+        let mut headers = header_map(&[
+            ("connection", "keep-alive, X-Session-Secret"),
+            ("keep-alive", "timeout=5"),
+            ("upgrade", "h2c"),
+            ("transfer-encoding", "chunked"),
+            ("proxy-authorization", "Basic abc"),
+            ("x-session-secret", "hunter2"),
+            ("accept", "*/*"),
+        ]);
+        remove_hop_by_hop(&mut headers);
+        assert_eq!(headers.keys().collect::<Vec<_>>(), ["accept"]);
+    }
+
+    #[test]
+    fn passes_te_trailers_only() {
+        let mut headers = header_map(&[("te", "gzip, trailers"), ("host", "example.com")]);
+        prepare_request(&mut headers, &route(false, false), false, &origin());
+        assert_eq!(headers.get("te").unwrap(), "trailers");
+        assert!(!headers.contains_key("host"));
+
+        let mut headers = header_map(&[("te", "gzip")]);
+        prepare_request(&mut headers, &route(false, false), false, &origin());
+        assert!(!headers.contains_key("te"));
+    }
+
+    #[test]
+    fn replaces_forwarding_headers_of_untrusted_clients() {
+        let mut headers = header_map(&[
+            ("x-forwarded-for", "10.0.0.1"),
+            ("x-forwarded-proto", "http"),
+            ("x-forwarded-host", "spoofed.example"),
+            ("forwarded", "for=10.0.0.1"),
+        ]);
+        prepare_request(&mut headers, &route(true, true), false, &origin());
+        assert_eq!(headers.get("x-forwarded-for").unwrap(), "203.0.113.7");
+        assert_eq!(headers.get("x-forwarded-proto").unwrap(), "https");
+        assert_eq!(headers.get("x-forwarded-host").unwrap(), "example.com");
+        assert_eq!(headers.get("forwarded").unwrap(), "for=203.0.113.7;proto=https;host=\"example.com\"");
+    }
+
+    #[test]
+    fn chains_forwarding_headers_of_trusted_proxies() {
+        let mut headers = header_map(&[
+            ("x-forwarded-for", "10.0.0.1"),
+            ("x-forwarded-for", "10.0.0.2"),
+            ("x-forwarded-proto", "http"),
+            ("forwarded", "for=10.0.0.1"),
+        ]);
+        prepare_request(&mut headers, &route(true, true), true, &origin());
+        assert_eq!(headers.get_all("x-forwarded-for").iter().collect::<Vec<_>>(), ["10.0.0.1, 10.0.0.2, 203.0.113.7"]);
+        assert_eq!(headers.get("x-forwarded-proto").unwrap(), "http");
+        assert_eq!(headers.get("x-forwarded-host").unwrap(), "example.com");
+        assert_eq!(headers.get("forwarded").unwrap(), "for=10.0.0.1, for=203.0.113.7;proto=https;host=\"example.com\"");
+    }
+
+    #[test]
+    fn leaves_forwarding_headers_to_the_route() {
+        let mut headers = header_map(&[("x-forwarded-for", "10.0.0.1")]);
+        prepare_request(&mut headers, &route(false, false), true, &origin());
+        assert_eq!(headers.get("x-forwarded-for").unwrap(), "10.0.0.1");
+        assert!(!headers.contains_key("forwarded"));
+    }
+
+    #[test]
+    fn quotes_ipv6_peers() {
+        let origin = Origin { peer: "2001:db8::1".parse().unwrap(), scheme: "http", host: None };
+        assert_eq!(forwarded_element(&origin), "for=\"[2001:db8::1]\";proto=http");
+    }
+}
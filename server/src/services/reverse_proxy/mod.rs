@@ -0,0 +1,218 @@
+mod headers;
+mod websocket;
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::time::Duration;
+use axum::{
+    Extension, Router,
+    body::Body,
+    routing::any,
+    extract::{ConnectInfo, Request, State, WebSocketUpgrade},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Version, uri::{PathAndQuery, Uri}},
+    response::{IntoResponse, Response},
+};
+use http_body_util::{LengthLimitError, Limited};
+use hyper::StatusCode;
+
+use crate::ServerContext;
+use crate::listener::{peer_addr, Scheme};
+use crate::config::server_config::{HeaderRules, PathRewrite, ProxyConfig, ProxyRoute, ServerConfig};
+
+pub fn setup_routes(router: Router<ServerContext>, config: &ServerConfig) -> Router<ServerContext> {
+    let mut router = router;
+    for route in config.proxy.routes.iter() {
+        let prefix = route.prefix.trim_end_matches('/');
+        router = router
+            .route(prefix, any(forward_to))
+            .route(&format!("{prefix}/*path"), any(forward_to));
+    }
+    router
+}
+
+/// The route with the longest prefix matching `path`, so that `/api/v2` takes
+/// precedence over `/api`.
+pub fn find_route<'a>(config: &'a ProxyConfig, path: &str) -> Option<&'a ProxyRoute> {
+    config.routes.iter()
+        .filter(|route| matches_prefix(path, &route.prefix))
+        .max_by_key(|route| route.prefix.trim_end_matches('/').len())
+}
+
+/// `/api` matches `/api` and `/api/users` but not `/apis`.
+fn matches_prefix(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix.trim_end_matches('/')) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Builds the upstream URI of a request according to the rewrite rule of its route.
+fn upstream_uri(route: &ProxyRoute, uri: &Uri) -> Option<Uri> {
+    let path_query = uri.path_and_query().map_or(uri.path(), PathAndQuery::as_str);
+    let prefix = route.prefix.trim_end_matches('/');
+    let path_query = match &route.rewrite {
+        PathRewrite::Strip => path_query.strip_prefix(prefix)?.to_string(),
+        PathRewrite::Keep => path_query.to_string(),
+        PathRewrite::Replace(replacement) => {
+            format!("{}{}", replacement.trim_end_matches('/'), path_query.strip_prefix(prefix)?)
+        }
+    };
+    let separator = if path_query.starts_with('/') { "" } else { "/" };
+    format!("{}{separator}{path_query}", route.forward_to.trim_end_matches('/')).parse().ok()
+}
+
+/// Whether a request body was cut off by [`Limited`] while being forwarded.
+fn is_body_too_large(err: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<LengthLimitError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+fn apply_header_rules(headers: &mut HeaderMap, rules: &HeaderRules) {
+    for name in rules.remove.iter() {
+        headers.remove(name.as_str());
+    }
+    for (name, value) in rules.set.iter() {
+        // both are checked when the configuration is loaded
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+async fn forward_to(
+    State(context): State<ServerContext>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    scheme: Option<Extension<Scheme>>,
+    ws: Option<WebSocketUpgrade>,
+    mut req: Request,
+) -> Result<Response, StatusCode> {
+    let config = context.config.load_full();
+    let route = find_route(&config.proxy, req.uri().path()).ok_or(StatusCode::NOT_FOUND)?;
+    let origin = headers::Origin {
+        peer: peer_addr(connect_info).ip(),
+        scheme: scheme.map_or("http", |Extension(Scheme(scheme))| scheme),
+        // HTTP/2 requests carry the host in the URI
+        host: req.headers().get(header::HOST).cloned()
+            .or_else(|| req.uri().authority().and_then(|authority| HeaderValue::try_from(authority.as_str()).ok())),
+    };
+    headers::prepare_request(req.headers_mut(), route, config.proxy.trust_forwarded, &origin);
+    let uri = upstream_uri(route, req.uri()).ok_or(StatusCode::BAD_REQUEST)?;
+    *req.uri_mut() = uri;
+    // the upstream is always spoken to in HTTP/1.1
+    *req.version_mut() = Version::HTTP_11;
+    apply_header_rules(req.headers_mut(), &route.request_headers);
+    if let Some(ws) = ws {
+        return websocket::forward(ws, &context, route, req).await;
+    }
+
+    // bodies are streamed to the upstream, those without a length are cut
+    // off once they exceed the limit
+    if route.max_body_size > 0 {
+        let content_length = req.headers().get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > route.max_body_size) {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        let max_body_size = route.max_body_size as usize;
+        req = req.map(|body| Body::new(Limited::new(body, max_body_size)));
+    }
+
+    let metrics = &context.metrics;
+    let timer = metrics.proxy_duration.with_label_values(&[&route.prefix]).start_timer();
+    let rsp = context.http_client.request(req);
+    let rsp = if route.timeout_ms > 0 {
+        tokio::time::timeout(Duration::from_millis(route.timeout_ms), rsp).await
+    } else {
+        Ok(rsp.await)
+    };
+    timer.observe_duration();
+    let status = match &rsp {
+        Ok(Ok(rsp)) => rsp.status().as_u16().to_string(),
+        Ok(Err(_)) => "error".to_string(),
+        Err(_) => "timeout".to_string(),
+    };
+    metrics.proxy_requests.with_label_values(&[&route.prefix, &status]).inc();
+
+    let mut rsp = match rsp {
+        Ok(Ok(rsp)) => rsp,
+        Ok(Err(err)) if is_body_too_large(&err) => return Err(StatusCode::PAYLOAD_TOO_LARGE),
+        Ok(Err(err)) => {
+            tracing::warn!("Proxy upstream {} failed: {err}", route.forward_to);
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        Err(_) => {
+            tracing::warn!("Proxy upstream {} timed out", route.forward_to);
+            return Err(StatusCode::GATEWAY_TIMEOUT);
+        }
+    };
+    headers::remove_hop_by_hop(rsp.headers_mut());
+    apply_header_rules(rsp.headers_mut(), &route.response_headers);
+    Ok(rsp.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn route(prefix: &str, rewrite: serde_json::Value) -> ProxyRoute {
+        serde_json::from_value(json!({
+            "prefix": prefix,
+            "forward_to": "http://upstream:9000",
+            "rewrite": rewrite,
+        })).unwrap()
+    }
+
+    fn proxy(prefixes: &[&str]) -> ProxyConfig {
+        ProxyConfig {
+            trust_forwarded: false,
+            routes: prefixes.iter().map(|prefix| route(prefix, json!("strip"))).collect(),
+        }
+    }
+
+    fn matched<'a>(config: &'a ProxyConfig, path: &str) -> Option<&'a str> {
+        find_route(config, path).map(|route| route.prefix.as_str())
+    }
+
+    #[test]
+    fn matches_the_longest_prefix() {
+        let config = proxy(&["/api", "/api/v2/", "/"]);
+        assert_eq!(matched(&config, "/api/v2/users"), Some("/api/v2/"));
+        assert_eq!(matched(&config, "/api/v2"), Some("/api/v2/"));
+        assert_eq!(matched(&config, "/api/v3"), Some("/api"));
+        assert_eq!(matched(&config, "/other"), Some("/"));
+    }
+
+    #[test]
+    fn matches_whole_segments_only() {
+        let config = proxy(&["/api"]);
+        assert_eq!(matched(&config, "/api"), Some("/api"));
+        assert_eq!(matched(&config, "/api/users"), Some("/api"));
+        assert_eq!(matched(&config, "/apis"), None);
+        assert_eq!(matched(&config, "/ap"), None);
+    }
+
+    #[test]
+    fn rewrites_paths_and_keeps_queries() {
+        let uri: Uri = "/api/users?page=2".parse().unwrap();
+        let rewritten = |rewrite| upstream_uri(&route("/api", rewrite), &uri).unwrap().to_string();
+        assert_eq!(rewritten(json!("strip")), "http://upstream:9000/users?page=2");
+        assert_eq!(rewritten(json!("keep")), "http://upstream:9000/api/users?page=2");
+        assert_eq!(rewritten(json!({"replace": "/v2"})), "http://upstream:9000/v2/users?page=2");
+    }
+
+    #[test]
+    fn rewrites_the_bare_prefix() {
+        let uri: Uri = "/api?page=2".parse().unwrap();
+        let rewritten = |rewrite| upstream_uri(&route("/api/", rewrite), &uri).unwrap().to_string();
+        assert_eq!(rewritten(json!("strip")), "http://upstream:9000/?page=2");
+        assert_eq!(rewritten(json!({"replace": "/v2/"})), "http://upstream:9000/v2?page=2");
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+use axum::{
+    body::Body,
+    extract::{Request, ws::{self, WebSocket, WebSocketUpgrade}},
+    http::{header, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream,
+    tungstenite::{self, client::IntoClientRequest, protocol::{frame::coding::CloseCode, CloseFrame}},
+};
+use tokio_util::sync::CancellationToken;
+use crate::ServerContext;
+use crate::config::server_config::ProxyRoute;
+use super::headers;
+
+type Upstream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Headers of the handshake between the client and the proxy, the handshake
+/// with the upstream has its own.
+const HANDSHAKE_HEADERS: &[&str] = &[
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "sec-websocket-extensions",
+    "sec-websocket-accept",
+];
+
+/// Opens a WebSocket to the upstream of `route`, then accepts the upgrade of
+/// the client and pipes the messages between both until either side closes.
+/// `req` is the request prepared for the upstream.
+pub async fn forward(
+    ws: WebSocketUpgrade,
+    context: &ServerContext,
+    route: &ProxyRoute,
+    req: Request,
+) -> Result<Response, StatusCode> {
+    let uri = websocket_uri(req.uri()).ok_or(StatusCode::BAD_REQUEST)?;
+    let mut request = uri.into_client_request().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let upstream_headers = request.headers_mut();
+    for name in req.headers().keys() {
+        if HANDSHAKE_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        upstream_headers.remove(name);
+        for value in req.headers().get_all(name) {
+            upstream_headers.append(name.clone(), value.clone());
+        }
+    }
+    // tungstenite matches the subprotocol chosen by the upstream against the
+    // offered ones split on `,` without trimming them
+    let offered: Vec<&str> = req.headers().get_all(header::SEC_WEBSOCKET_PROTOCOL).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|protocol| !protocol.is_empty())
+        .collect();
+    if !offered.is_empty() {
+        let offered = HeaderValue::try_from(offered.join(",")).map_err(|_| StatusCode::BAD_REQUEST)?;
+        upstream_headers.insert(header::SEC_WEBSOCKET_PROTOCOL, offered);
+    }
+
+    let metrics = &context.metrics;
+    let timer = metrics.proxy_duration.with_label_values(&[&route.prefix]).start_timer();
+    let connect = tokio_tungstenite::connect_async(request);
+    let connected = if route.timeout_ms > 0 {
+        tokio::time::timeout(Duration::from_millis(route.timeout_ms), connect).await
+    } else {
+        Ok(connect.await)
+    };
+    timer.observe_duration();
+    let status = match &connected {
+        Ok(Ok(_)) => StatusCode::SWITCHING_PROTOCOLS.as_u16().to_string(),
+        Ok(Err(tungstenite::Error::Http(rsp))) => rsp.status().as_u16().to_string(),
+        Ok(Err(_)) => "error".to_string(),
+        Err(_) => "timeout".to_string(),
+    };
+    metrics.proxy_requests.with_label_values(&[&route.prefix, &status]).inc();
+
+    let (upstream, rsp) = match connected {
+        Ok(Ok(connected)) => connected,
+        // the upstream refused the upgrade, the client gets its answer
+        Ok(Err(tungstenite::Error::Http(rsp))) => {
+            let (mut parts, body) = rsp.into_parts();
+            headers::remove_hop_by_hop(&mut parts.headers);
+            return Ok(Response::from_parts(parts, Body::from(body.unwrap_or_default())));
+        }
+        Ok(Err(err)) => {
+            tracing::warn!("Proxy upstream {} failed to open a WebSocket: {err}", route.forward_to);
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        Err(_) => {
+            tracing::warn!("Proxy upstream {} timed out opening a WebSocket", route.forward_to);
+            return Err(StatusCode::GATEWAY_TIMEOUT);
+        }
+    };
+
+    // the upstream picked one of the subprotocols offered by the client
+    let mut ws = ws;
+    if let Some(protocol) = rsp.headers().get(header::SEC_WEBSOCKET_PROTOCOL).and_then(|value| value.to_str().ok()) {
+        ws = ws.protocols([protocol.to_string()]);
+    }
+    let shutdown = context.shutdown.clone();
+    Ok(ws.on_upgrade(move |socket| pipe(socket, upstream, shutdown)).into_response())
+}
+
+/// The `http` upstream URI with the `ws` scheme, upstreams are never `https`.
+fn websocket_uri(uri: &Uri) -> Option<Uri> {
+    let path_query = uri.path_and_query().map_or("/", |path_query| path_query.as_str());
+    format!("ws://{}{path_query}", uri.authority()?).parse().ok()
+}
+
+/// Forwards the messages in both directions. A close frame is passed on to
+/// the other side, which then answers it, and the pipe ends once either side
+/// is gone.
+async fn pipe(mut client: WebSocket, mut upstream: Upstream, shutdown: CancellationToken) {
+    loop {
+        tokio::select! {
+            msg = client.recv() => match msg {
+                Some(Ok(msg)) => {
+                    let Some(msg) = to_upstream(msg) else { continue };
+                    if upstream.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {
+                    let _ = upstream.close(None).await;
+                    break;
+                }
+            },
+            msg = upstream.next() => match msg {
+                Some(Ok(msg)) => {
+                    let Some(msg) = to_client(msg) else { continue };
+                    if client.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                _ => {
+                    let _ = client.close().await;
+                    break;
+                }
+            },
+            _ = shutdown.cancelled() => {
+                let reason = "Server is shutting down";
+                let _ = client.send(ws::Message::Close(Some(ws::CloseFrame {
+                    code: ws::close_code::AWAY,
+                    reason: reason.into(),
+                }))).await;
+                let _ = upstream.close(Some(CloseFrame { code: CloseCode::Away, reason: reason.into() })).await;
+                break;
+            }
+        }
+    }
+    tracing::debug!("Proxied WebSocket closed");
+}
+
+// pings and pongs are answered on each side by the WebSocket implementations
+fn to_upstream(msg: ws::Message) -> Option<tungstenite::Message> {
+    match msg {
+        ws::Message::Text(text) => Some(tungstenite::Message::Text(text)),
+        ws::Message::Binary(data) => Some(tungstenite::Message::Binary(data)),
+        ws::Message::Close(frame) => Some(tungstenite::Message::Close(frame.map(|frame| CloseFrame {
+            code: CloseCode::from(frame.code),
+            reason: frame.reason,
+        }))),
+        ws::Message::Ping(_) | ws::Message::Pong(_) => None,
+    }
+}
+
+fn to_client(msg: tungstenite::Message) -> Option<ws::Message> {
+    match msg {
+        tungstenite::Message::Text(text) => Some(ws::Message::Text(text)),
+        tungstenite::Message::Binary(data) => Some(ws::Message::Binary(data)),
+        tungstenite::Message::Close(frame) => Some(ws::Message::Close(frame.map(|frame| ws::CloseFrame {
+            code: frame.code.into(),
+            reason: frame.reason,
+        }))),
+        tungstenite::Message::Ping(_) | tungstenite::Message::Pong(_) | tungstenite::Message::Frame(_) => None,
+    }
+}
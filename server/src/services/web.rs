@@ -7,12 +7,11 @@ use axum::{
 };
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
-use std::boxed::Box;
 use crate::ServerContext;
-use crate::config::SERVER_CONFIG;
+use crate::config::server_config::{HTTPConfig, ServerConfig};
 
-pub fn setup_routes(router: Router<ServerContext>) -> Router<ServerContext> {
-    let base_path = SERVER_CONFIG.http.base_path.as_str();
+pub fn setup_routes(router: Router<ServerContext>, config: &ServerConfig) -> Router<ServerContext> {
+    let base_path = config.http.base_path.as_str();
     let base_path_continue = if base_path.ends_with("/") {
         format!("{base_path}")
     } else {
@@ -20,27 +19,30 @@ pub fn setup_routes(router: Router<ServerContext>) -> Router<ServerContext> {
     };
     let index_path = format!("{base_path_continue}index.html");
     let get_file_path = format!("{base_path_continue}*path");
-    let index_path: &'static str = Box::leak(index_path.into_boxed_str());
     let get_file_path = get_file_path.as_str();
 
     router
-        .route(base_path, get(|| async { Redirect::permanent(index_path) }))
+        .route(base_path, get(|| async move { Redirect::permanent(&index_path) }))
         .route(get_file_path, get(get_file))
 }
 
 async fn get_file(
-    State(_): State<ServerContext>,
+    State(context): State<ServerContext>,
     uri: Uri,
 ) -> Result<Response, StatusCode> {
+    let config = context.config.load_full();
     let path = uri.path()
-        .trim_start_matches(SERVER_CONFIG.http.base_path.as_str())
+        .trim_start_matches(config.http.base_path.as_str())
         .trim_start_matches("/")
         .to_string();
-    serve_file_by_path(path).await
+    let rsp = serve_file_by_path(&config.http, path).await;
+    let result = if rsp.is_ok() { "hit" } else { "miss" };
+    context.metrics.static_files.with_label_values(&[result]).inc();
+    rsp
 }
 
-async fn serve_file_by_path(path: String) -> Result<Response, StatusCode> {
-    let dist_path = SERVER_CONFIG.http.dist_path.as_str();
+async fn serve_file_by_path(config: &HTTPConfig, path: String) -> Result<Response, StatusCode> {
+    let dist_path = config.dist_path.as_str();
     let file_path = format!("{dist_path}/{path}");
 
     let mut file = match File::open(&file_path).await {
@@ -54,10 +56,17 @@ async fn serve_file_by_path(path: String) -> Result<Response, StatusCode> {
     }
 
     let mime_type = mime_guess::from_path(&file_path).first_or_octet_stream();
+    // index.html names the hashed assets of the current build, always revalidate it
+    let cache_control = if config.cache_max_age_secs == 0 || path.ends_with("index.html") {
+        "no-cache".to_string()
+    } else {
+        format!("public, max-age={}", config.cache_max_age_secs)
+    };
 
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", mime_type.as_ref())
+        .header("Cache-Control", cache_control)
         .body(contents.into())
         .unwrap())
 }
@@ -1,21 +1,93 @@
+use std::net::SocketAddr;
 use axum::{
-    extract::{ws::WebSocketUpgrade, State},
-    response::Response,
+    extract::{ws::WebSocketUpgrade, ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
     routing::get,
     Router,
 };
-use crate::config::SERVER_CONFIG;
+use serde::Deserialize;
+use crate::auth::extract_token;
+use crate::config::server_config::ServerConfig;
 use crate::ServerContext;
-use crate::net::gateway::handle_socket;
+use crate::listener::peer_addr;
+use crate::net::gateway::{handle_socket, Handshake};
+use crate::net::protocol::Protocol;
 
-pub fn setup_routes(router: Router<ServerContext>) -> Router<ServerContext> {
+pub fn setup_routes(router: Router<ServerContext>, config: &ServerConfig) -> Router<ServerContext> {
+    if config.websocket.allowed_origins.iter().any(|origin| origin == "*") {
+        tracing::warn!("WebSocket upgrades are accepted from any origin, do not use this in production");
+    }
     router
-        .route(SERVER_CONFIG.websocket.base_path.as_str(), get(websocket_handler))
+        .route(config.websocket.base_path.as_str(), get(websocket_handler))
+}
+
+#[derive(Deserialize)]
+struct WebsocketParams {
+    token: Option<String>,
+    resume_token: Option<String>,
+    last_seq: Option<u64>,
 }
 
 async fn websocket_handler(
     ws: WebSocketUpgrade,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(params): Query<WebsocketParams>,
     State(state): State<ServerContext>
 ) -> Response {
-    ws.on_upgrade(| socket | handle_socket(socket, state))
+    let config = state.config.load_full();
+    if !is_origin_allowed(&headers, &config.websocket.allowed_origins) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let token = extract_token(&headers, params.token.as_deref(), &config.auth.cookie_name);
+    let Some(identity) = state.authenticator.authenticate(token.as_deref()) else {
+        tracing::warn!("Rejected unauthenticated WebSocket upgrade");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    // clients that offer no subprotocol at all keep the legacy behaviour
+    let mut subprotocol = None;
+    let mut ws = ws;
+    if let Some(offered) = headers.get(header::SEC_WEBSOCKET_PROTOCOL) {
+        let offered = offered.to_str().unwrap_or_default();
+        let Some((name, protocol)) = Protocol::negotiate(offered) else {
+            tracing::warn!("Rejected WebSocket upgrade with unsupported subprotocols: {offered}");
+            return (StatusCode::BAD_REQUEST, "Unsupported WebSocket subprotocol").into_response();
+        };
+        ws = ws.protocols([name]);
+        subprotocol = Some(protocol);
+    }
+    let handshake = Handshake {
+        peer: peer_addr(connect_info),
+        subprotocol,
+        identity,
+        resume_token: params.resume_token,
+        last_seq: params.last_seq,
+    };
+    ws.on_upgrade(move | socket | handle_socket(socket, state, handshake))
+}
+
+/// Guards against cross-site WebSocket hijacking, since browsers send cookies
+/// along with cross-site upgrades. Requests without `Origin` do not come from
+/// a browser and are let through.
+fn is_origin_allowed(headers: &HeaderMap, allowed_origins: &[String]) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let origin = origin.to_str().unwrap_or_default();
+
+    let allowed = if allowed_origins.is_empty() {
+        let host = headers.get(header::HOST).and_then(|host| host.to_str().ok());
+        let authority = origin.parse::<Uri>().ok()
+            .and_then(|uri| uri.authority().map(|authority| authority.to_string()));
+        host.is_some() && authority.as_deref() == host
+    } else {
+        allowed_origins.iter().any(|allowed| allowed == "*" || allowed.trim_end_matches('/') == origin)
+    };
+    if !allowed {
+        tracing::warn!("Rejected WebSocket upgrade from origin {origin}");
+    }
+    allowed
 }
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    Router,
+    http::{header, HeaderMap, Uri},
+    response::Redirect,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::version::{TLS12, TLS13};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use crate::config::server_config::{TlsConfig, TlsVersion};
+
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+pub fn load_rustls_config(config: &TlsConfig) -> Result<RustlsConfig> {
+    Ok(RustlsConfig::from_config(Arc::new(build_server_config(config)?)))
+}
+
+fn build_server_config(config: &TlsConfig) -> Result<rustls::ServerConfig> {
+    let mut cert_file = BufReader::new(File::open(&config.cert_path)
+        .with_context(|| format!("Could not open certificate {}", config.cert_path))?);
+    let certs = rustls_pemfile::certs(&mut cert_file)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate {}", config.cert_path))?;
+
+    let mut key_file = BufReader::new(File::open(&config.key_path)
+        .with_context(|| format!("Could not open private key {}", config.key_path))?);
+    let key = rustls_pemfile::private_key(&mut key_file)
+        .with_context(|| format!("Invalid private key {}", config.key_path))?
+        .ok_or_else(|| anyhow!("No private key found in {}", config.key_path))?;
+
+    let versions: &[&rustls::SupportedProtocolVersion] = match config.min_version {
+        TlsVersion::Tls12 => &[&TLS13, &TLS12],
+        TlsVersion::Tls13 => &[&TLS13],
+    };
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut server_config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(versions)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    server_config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|protocol| protocol.to_vec()).collect();
+    Ok(server_config)
+}
+
+fn modified_at(config: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(&config.cert_path).and_then(|meta| meta.modified()).ok()?;
+    let key = std::fs::metadata(&config.key_path).and_then(|meta| meta.modified()).ok()?;
+    Some((cert, key))
+}
+
+/// Polls the certificate and key files and swaps in the new certificate when
+/// they change, so renewed certificates apply without dropping connections.
+pub fn watch_certificates(rustls: RustlsConfig, config: TlsConfig, shutdown: CancellationToken) {
+    if config.reload_interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.reload_interval_secs));
+        let mut last_modified = modified_at(&config);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return,
+            }
+            let modified = modified_at(&config);
+            if modified.is_none() || modified == last_modified {
+                continue;
+            }
+            match build_server_config(&config) {
+                Ok(server_config) => {
+                    rustls.reload_from_config(Arc::new(server_config));
+                    last_modified = modified;
+                    tracing::info!("Reloaded TLS certificate {}", config.cert_path);
+                }
+                // the files may be halfway written, retry on the next tick
+                Err(err) => tracing::warn!("Failed to reload TLS certificate: {err:#}"),
+            }
+        }
+    });
+}
+
+/// Serves a plain HTTP listener redirecting every request to HTTPS.
+pub async fn redirect_http_to_https(addr: String, https_port: u32, shutdown: CancellationToken) -> Result<()> {
+    let redirect = move |headers: HeaderMap, uri: Uri| async move {
+        let host = headers.get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or("localhost");
+        let host = host.parse::<Uri>().ok()
+            .and_then(|uri| uri.host().map(str::to_string))
+            .unwrap_or_else(|| host.to_string());
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+        let location = if https_port == 443 {
+            format!("https://{host}{path}")
+        } else {
+            format!("https://{host}:{https_port}{path}")
+        };
+        Redirect::permanent(&location)
+    };
+
+    let listener = TcpListener::bind(&addr).await?;
+    tracing::info!("Redirecting HTTP requests at {addr} to HTTPS");
+    axum::serve(listener, Router::new().fallback(redirect))
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+    Ok(())
+}
@@ -1,6 +1,101 @@
-pub fn init_tracing() {
+use anyhow::Result;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    fmt::{self, MakeWriter},
+    layer::Layered,
+    prelude::*,
+    reload, EnvFilter, Layer, Registry,
+};
+use crate::config::server_config::{LogConfig, LogFormat, LogRotation};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+type FilterHandle = reload::Handle<EnvFilter, Layered<Vec<BoxedLayer>, Registry>>;
+
+/// Handle changing the log filter of the running server.
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: FilterHandle,
+    // `RUST_LOG` takes precedence over the configuration
+    from_env: bool,
+}
+
+impl LogFilter {
+    pub fn set(&self, filter: &str) -> Result<()> {
+        if self.from_env {
+            tracing::warn!("Log filter is set by RUST_LOG, log.filter is not applied");
+            return Ok(());
+        }
+        self.handle.reload(EnvFilter::try_new(filter)?)?;
+        Ok(())
+    }
+}
+
+/// Installs the global subscriber. The returned guard flushes the log file
+/// when dropped and must be kept alive until the server exits.
+pub fn init_tracing(config: &LogConfig) -> Result<(Option<WorkerGuard>, LogFilter)> {
     #[cfg(target_os = "windows")]
     ansi_term::enable_ansi_support().unwrap();
 
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let (filter, from_env) = match EnvFilter::try_from_default_env() {
+        Ok(filter) => (filter, true),
+        Err(_) => (EnvFilter::try_new(&config.filter)?, false),
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let mut layers = vec![format_layer(config.format, std::io::stdout, true)];
+    let mut guard = None;
+    if let Some(file) = &config.file {
+        let rotation = match file.rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        };
+        let appender = RollingFileAppender::new(rotation, &file.directory, &file.prefix);
+        let (writer, worker_guard) = tracing_appender::non_blocking(appender);
+        layers.push(format_layer(config.format, writer, false));
+        guard = Some(worker_guard);
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()?;
+    Ok((guard, LogFilter { handle, from_env }))
+}
+
+fn format_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer.json().with_current_span(true).with_span_list(true).boxed(),
+    }
+}
+
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await
+            .expect("Failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
@@ -0,0 +1,77 @@
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use futures_util::StreamExt;
+use hyper_util::{client::legacy::{connect::HttpConnector, Client}, rt::TokioExecutor};
+use server::config::default_config;
+use server::config::server_config::ServerConfig;
+use server::ServerContext;
+use tokio::net::TcpListener;
+
+/// Serves an application the way the README suggests, without connect info.
+async fn serve(config: ServerConfig) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, server::build_app(config)).await.unwrap();
+    });
+    addr
+}
+
+async fn get(addr: SocketAddr, path: &str) -> StatusCode {
+    let client: Client<HttpConnector, Body> = Client::builder(TokioExecutor::new()).build_http();
+    let req = Request::get(format!("http://{addr}{path}")).body(Body::empty()).unwrap();
+    client.request(req).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn apps_with_different_configs_run_side_by_side() {
+    let mut first = default_config();
+    first.http.dist_path = env!("CARGO_MANIFEST_DIR").to_string();
+    first.health.liveness_path = "/first/live".to_string();
+    first.proxy.routes[0].prefix = "/first/proxy".to_string();
+    // nothing listens on port 1, the upstream is unreachable
+    first.proxy.routes[0].forward_to = "http://127.0.0.1:1".to_string();
+    let mut second = default_config();
+    second.health.liveness_path = "/second/live".to_string();
+    second.websocket.base_path = "/second/ws".to_string();
+    second.proxy.routes.clear();
+    second.http.dist_path = env!("CARGO_MANIFEST_DIR").to_string();
+
+    let first = serve(first).await;
+    let second = serve(second).await;
+
+    assert_eq!(get(first, "/first/live").await, StatusCode::OK);
+    assert_eq!(get(second, "/second/live").await, StatusCode::OK);
+    assert_eq!(get(first, "/second/live").await, StatusCode::NOT_FOUND);
+    assert_eq!(get(second, "/first/live").await, StatusCode::NOT_FOUND);
+
+    // ready once served, unless a check fails like the unreachable upstream
+    assert_eq!(get(second, "/readyz").await, StatusCode::OK);
+    assert_eq!(get(first, "/readyz").await, StatusCode::SERVICE_UNAVAILABLE);
+
+    // the handlers needing the peer address work without connect info
+    assert_eq!(get(first, "/first/proxy/users").await, StatusCode::BAD_GATEWAY);
+    assert_eq!(get(second, "/first/proxy/users").await, StatusCode::NOT_FOUND);
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{second}/second/ws")).await.unwrap();
+    assert!(ws.next().await.unwrap().unwrap().is_binary());
+}
+
+#[tokio::test]
+async fn router_reports_the_state_of_its_context() {
+    let mut config = default_config();
+    config.proxy.routes.clear();
+    config.http.dist_path = env!("CARGO_MANIFEST_DIR").to_string();
+    let context = ServerContext::new(config);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, server::router(context.clone())).into_future());
+
+    assert_eq!(get(addr, "/readyz").await, StatusCode::SERVICE_UNAVAILABLE);
+    context.listening.store(true, Ordering::Relaxed);
+    assert_eq!(get(addr, "/readyz").await, StatusCode::OK);
+    context.shutdown.cancel();
+    assert_eq!(get(addr, "/readyz").await, StatusCode::SERVICE_UNAVAILABLE);
+}
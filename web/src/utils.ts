@@ -1,22 +1,26 @@
 import ByteBuffer from 'bytebuffer'
 
+// Subprotocol
+export const WS_SUBPROTOCOL = 'fate-loom.v2'
+
 // Packet
 const HEAD_MAGIC = 0x46415445; // FATE
 const TAIL_MAGIC = 0x4C4F4F4D; // LOOM
 
-export const encodePacket = (cmdID: number, msg: Uint8Array): ArrayBuffer => {
+export const encodePacket = (cmdID: number, msg: Uint8Array, seq: number = 0): ArrayBuffer => {
   const len = msg.length
-  const buffer = new ByteBuffer(12 + len, true)
+  const buffer = new ByteBuffer(22 + len, true)
   buffer.order(ByteBuffer.LITTLE_ENDIAN)
   buffer.writeUint32(HEAD_MAGIC)
   buffer.writeUint16(cmdID)
+  buffer.writeUint64(seq)
   buffer.writeUint32(len)
   buffer.append(msg)
   buffer.writeUint32(TAIL_MAGIC)
   return buffer.buffer
 }
 
-export const decodePacket = (packet: ArrayBuffer): [number, Uint8Array] => {
+export const decodePacket = (packet: ArrayBuffer): [number, Uint8Array, number] => {
   const buffer = ByteBuffer.wrap(packet, true)
   buffer.order(ByteBuffer.LITTLE_ENDIAN)
   const headMagic = buffer.readUint32()
@@ -24,13 +28,14 @@ export const decodePacket = (packet: ArrayBuffer): [number, Uint8Array] => {
     throw new Error('Invalid head magic')
   }
   const cmdID = buffer.readUint16()
+  const seq = buffer.readUint64().toNumber()
   const len = buffer.readUint32()
   const msg = buffer.readBytes(len).toArrayBuffer()
   const tailMagic = buffer.readUint32()
   if (tailMagic !== TAIL_MAGIC) {
     throw new Error('Invalid tail magic')
   }
-  return [cmdID, new Uint8Array(msg)]
+  return [cmdID, new Uint8Array(msg), seq]
 }
 
 // CMD ID
@@ -45,3 +50,6 @@ export const STOP_RANDOM_NUMBER_RESPONSE = 8;
 export const INCREMENTAL_SEQUENCE_RESPONSE = 9;
 export const STOP_INCREMENTAL_SEQUENCE_RESPONSE = 10;
 export const ECHO_RESPONSE = 11;
+export const SESSION_INFO_NOTIFY = 12;
+export const SERVER_SHUTDOWN_NOTICE = 13;
+export const COMMAND_STATUS_NOTIFY = 14;
@@ -3,7 +3,7 @@ import { ElMessage } from 'element-plus'
 import {
   decodePacket, INCREMENTAL_SEQUENCE_RESPONSE,
   RANDOM_NUMBER_RESPONSE, STOP_INCREMENTAL_SEQUENCE_RESPONSE,
-  STOP_RANDOM_NUMBER_RESPONSE,
+  STOP_RANDOM_NUMBER_RESPONSE, WS_SUBPROTOCOL,
 } from './utils'
 import {
   decodeIncrementalSequenceResponse,
@@ -17,7 +17,7 @@ import {
 export const ws: Ref<WebSocket | null> = ref(null)
 
 export const initWebSocket = (url: string) => {
-  ws.value = new WebSocket(url)
+  ws.value = new WebSocket(url, WS_SUBPROTOCOL)
 
   ws.value.onopen = () => {
     ElMessage({